@@ -0,0 +1,96 @@
+// @format
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Replaces the file at `path` with `contents` so that it's never left partially written.
+///
+/// The contents are written to a temporary file next to `path`, synced to disk and then renamed
+/// over `path`, creating the parent directory first if needed.
+pub fn write(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    fs::create_dir_all(dir)?;
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let temp_path = dir.join(format!(".{}{}", file_name.to_string_lossy(), temp_suffix()));
+
+    let result =
+        write_synced(&temp_path, contents.as_ref()).and_then(|_| fs::rename(&temp_path, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&temp_path);
+        return result;
+    }
+    sync_dir(dir)
+}
+
+/// Returns the end of the names of the temporary files written by this process.
+fn temp_suffix() -> String {
+    format!(".{}.tmp", std::process::id())
+}
+
+/// Removes the temporary files this process left in `dir`, e.g. when it's interrupted while
+/// writing.
+pub fn remove_leftovers(dir: &Path) {
+    let suffix = temp_suffix();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') && name.ends_with(&suffix) {
+            let _ = fs::remove_file(entry.path());
+        }
+    }
+}
+
+/// Writes `contents` to a new file at `path` and waits until they are on disk.
+fn write_synced(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+/// Syncs a directory so that a rename within it survives a crash.
+#[cfg(unix)]
+fn sync_dir(dir: &Path) -> io::Result<()> {
+    File::open(dir)?.sync_all()
+}
+
+/// Directories can't be synced on this platform, renames are durable once they return.
+#[cfg(not(unix))]
+fn sync_dir(_dir: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_contents_without_leftovers() {
+        let dir = std::env::temp_dir().join(format!("kiwistand-atomic-{}", std::process::id()));
+        let path = dir.join("state.json");
+        write(&path, "first").unwrap();
+        write(&path, "second").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "second");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn removes_only_its_own_leftovers() {
+        let dir = std::env::temp_dir().join(format!("kiwistand-leftovers-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(format!(".state.json{}", temp_suffix())), "partial").unwrap();
+        fs::write(dir.join(".state.json.1.tmp"), "another process").unwrap();
+        remove_leftovers(&dir);
+        let names: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name()).collect();
+        assert_eq!(names, [".state.json.1.tmp"]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
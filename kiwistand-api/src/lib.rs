@@ -0,0 +1,7 @@
+// @format
+//! The HTTP client the CLI talks to nodes and fetches pages with, and the atomic file writes
+//! its fixtures and the CLI's state files are stored with.
+pub mod atomic;
+mod transport;
+
+pub use transport::{Observer, Response, Transport};
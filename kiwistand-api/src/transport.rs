@@ -0,0 +1,296 @@
+// @format
+use reqwest::{
+    header::{HeaderMap, HeaderValue, CONTENT_TYPE, FROM},
+    Method,
+};
+use serde_json::{json, Value};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::atomic;
+
+/// The User-Agent requests are identified with unless another one is configured.
+const DEFAULT_USER_AGENT: &str = concat!("kiwistand-cli/", env!("CARGO_PKG_VERSION"));
+
+/// How long to wait between two page fetches from the same host.
+const HOST_INTERVAL: Duration = Duration::from_secs(1);
+
+/// `Observer` is told about every request a `Transport` sent, e.g. to profile a command.
+pub trait Observer: Send + Sync {
+    /// Called once the request described by `request` finished, after it started at `start`.
+    fn request_finished(&self, request: String, start: Instant);
+}
+
+/// Where responses come from and whether they are recorded.
+enum Mode {
+    Live,
+    Record(PathBuf),
+    Replay(PathBuf),
+}
+
+/// `Transport` performs the CLI's HTTP requests, optionally recording them to or replaying them
+/// from a fixtures directory.
+///
+/// Fixtures are numbered in the order the requests are made, as messages contain timestamps and
+/// signatures that change between runs.
+pub struct Transport {
+    client: reqwest::Client,
+    mode: Mode,
+    sequence: AtomicUsize,
+    observer: Arc<dyn Observer>,
+    no_fetch: bool,
+    next_fetch: Mutex<HashMap<String, Instant>>,
+}
+
+/// The status and body of a response.
+pub struct Response {
+    pub status: u16,
+    pub body: String,
+}
+
+impl Response {
+    /// Returns whether the status is in the 2xx range.
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+impl Transport {
+    /// Creates a transport identifying itself with `user_agent`, or `kiwistand-cli/<version>`.
+    ///
+    /// If `contact` is given, it's sent as the `From` header of every request, so node operators
+    /// can reach whoever runs an automated deployment.
+    pub fn new(
+        record: Option<PathBuf>,
+        replay: Option<PathBuf>,
+        user_agent: Option<String>,
+        contact: Option<String>,
+        no_fetch: bool,
+        observer: Arc<dyn Observer>,
+    ) -> Transport {
+        let mode = match (record, replay) {
+            (Some(dir), _) => Mode::Record(dir),
+            (None, Some(dir)) => Mode::Replay(dir),
+            (None, None) => Mode::Live,
+        };
+        let mut headers = HeaderMap::new();
+        if let Some(contact) = contact {
+            let contact = HeaderValue::from_str(&contact)
+                .unwrap_or_else(|_| panic!("The contact {:?} isn't a valid header value", contact));
+            headers.insert(FROM, contact);
+        }
+        let user_agent = user_agent.unwrap_or_else(|| String::from(DEFAULT_USER_AGENT));
+        let user_agent = HeaderValue::from_str(&user_agent).unwrap_or_else(|_| {
+            panic!("The user agent {:?} isn't a valid header value", user_agent)
+        });
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .default_headers(headers)
+            .build()
+            .expect("Couldn't build the HTTP client");
+        Transport {
+            client,
+            mode,
+            sequence: AtomicUsize::new(0),
+            observer,
+            no_fetch,
+            next_fetch: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sends a GET request to `url`.
+    pub async fn get(&self, url: &str) -> Result<Response, reqwest::Error> {
+        self.request(Method::GET, url, None).await
+    }
+
+    /// Fetches a third-party page, such as a submitted link.
+    ///
+    /// Unlike `get`, this fails if fetching pages was disabled with `--no-fetch`, and waits so
+    /// that the same host isn't requested more than once per `HOST_INTERVAL`.
+    pub async fn get_page(&self, url: &str) -> Result<Response, reqwest::Error> {
+        if self.no_fetch {
+            panic!("Fetching {} is disabled by --no-fetch", url);
+        }
+        if !matches!(self.mode, Mode::Replay(_)) {
+            if let Some(host) = reqwest::Url::parse(url)
+                .ok()
+                .and_then(|url| url.host_str().map(String::from))
+            {
+                let wait = self.reserve_fetch(host);
+                tokio::time::sleep(wait).await;
+            }
+        }
+        self.get(url).await
+    }
+
+    /// Reserves the next fetch slot for `host` and returns how long to wait for it.
+    fn reserve_fetch(&self, host: String) -> Duration {
+        let now = Instant::now();
+        let mut next_fetch = self.next_fetch.lock().unwrap();
+        let slot = match next_fetch.get(&host) {
+            Some(next) if *next > now => *next,
+            _ => now,
+        };
+        next_fetch.insert(host, slot + HOST_INTERVAL);
+        slot - now
+    }
+
+    /// Sends `body` as JSON in a POST request to `url`.
+    pub async fn post_json(&self, url: &str, body: String) -> Result<Response, reqwest::Error> {
+        self.request(Method::POST, url, Some(body)).await
+    }
+
+    async fn request(
+        &self,
+        method: Method,
+        url: &str,
+        body: Option<String>,
+    ) -> Result<Response, reqwest::Error> {
+        let sequence = self.sequence.fetch_add(1, Ordering::SeqCst) + 1;
+        let fixture_name = format!("{:04}.json", sequence);
+
+        if let Mode::Replay(dir) = &self.mode {
+            let path = dir.join(&fixture_name);
+            let contents = fs::read_to_string(&path)
+                .unwrap_or_else(|_| panic!("There is no fixture {} to replay", path.display()));
+            let fixture: Value = serde_json::from_str(&contents).expect("Couldn't parse fixture");
+            if fixture["method"] != method.as_str() || fixture["url"] != url {
+                panic!(
+                    "Fixture {} was recorded for {} {} instead of {} {}",
+                    path.display(),
+                    fixture["method"],
+                    fixture["url"],
+                    method,
+                    url
+                );
+            }
+            return Ok(Response {
+                status: fixture["status"].as_u64().expect("Fixture without status") as u16,
+                body: fixture["response"].as_str().unwrap_or_default().to_string(),
+            });
+        }
+
+        let start = Instant::now();
+        let mut request = self.client.request(method.clone(), url);
+        if let Some(body) = &body {
+            request = request
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone());
+        }
+        let response = request.send().await?;
+        let response = Response {
+            status: response.status().as_u16(),
+            body: response.text().await?,
+        };
+        self.observer.request_finished(format!("http {} {}", method, url), start);
+
+        if let Mode::Record(dir) = &self.mode {
+            let fixture = json!({
+                "method": method.as_str(),
+                "url": url,
+                "request": body,
+                "status": response.status,
+                "response": response.body,
+            });
+            let contents = serde_json::to_string_pretty(&fixture).unwrap();
+            atomic::write(&dir.join(&fixture_name), contents).expect("Couldn't write fixture");
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Ignores every request.
+    struct Ignore;
+
+    impl Observer for Ignore {
+        fn request_finished(&self, _request: String, _start: Instant) {}
+    }
+
+    fn transport(record: Option<PathBuf>, replay: Option<PathBuf>) -> Transport {
+        Transport::new(record, replay, None, None, false, Arc::new(Ignore))
+    }
+
+    /// Returns an empty directory for a test's fixtures.
+    fn fixtures_dir(name: &str) -> PathBuf {
+        let name = format!("kiwistand-api-{}-{}", name, std::process::id());
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Answers a single HTTP request with `body`, and returns the URL to send it to.
+    fn serve_once(body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/messages", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request);
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+        url
+    }
+
+    #[tokio::test]
+    async fn replays_recorded_requests() {
+        let dir = fixtures_dir("round-trip");
+        let url = serve_once("{\"status\":\"success\"}");
+        let recorded = transport(Some(dir.clone()), None).get(&url).await.unwrap();
+        assert_eq!(recorded.status, 200);
+        assert_eq!(recorded.body, "{\"status\":\"success\"}");
+
+        let fixture = fs::read_to_string(dir.join("0001.json")).unwrap();
+        let fixture: Value = serde_json::from_str(&fixture).unwrap();
+        assert_eq!(fixture["method"], "GET");
+        assert_eq!(fixture["url"], url);
+
+        // The server is gone, so the response can only come from the fixture.
+        let replayed = transport(None, Some(dir.clone())).get(&url).await.unwrap();
+        assert_eq!(replayed.status, recorded.status);
+        assert_eq!(replayed.body, recorded.body);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "There is no fixture")]
+    async fn rejects_missing_fixtures() {
+        let dir = fixtures_dir("missing");
+        let _ = transport(None, Some(dir)).get("https://example.com").await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "was recorded for")]
+    async fn rejects_fixtures_of_other_requests() {
+        let dir = fixtures_dir("mismatch");
+        let fixture = json!({
+            "method": "GET",
+            "url": "https://example.com",
+            "status": 200,
+            "response": "",
+        });
+        atomic::write(&dir.join("0001.json"), fixture.to_string()).unwrap();
+        let body = String::from("{}");
+        let _ = transport(None, Some(dir))
+            .post_json("https://example.com", body)
+            .await;
+    }
+}
@@ -0,0 +1,65 @@
+// @format
+use ethers::core::types::Signature;
+
+use crate::Message;
+
+/// Serializes a signed message exactly as the JS client does.
+///
+/// The fields appear in the order `title`, `href`, `type`, `timestamp`, `signature`, the timestamp
+/// is a plain decimal integer and the signature is lowercase hex with a `0x` prefix. Strings are
+/// escaped like `JSON.stringify` does, which is what `serde_json` implements.
+pub fn serialize(message: &Message, signature: &Signature) -> String {
+    format!(
+        "{{\"title\":{},\"href\":{},\"type\":{},\"timestamp\":{},\"signature\":{}}}",
+        string(&message.title),
+        string(&message.href),
+        string(&message.r#type),
+        message.timestamp,
+        string(&format!("0x{}", signature)),
+    )
+}
+
+/// Returns the quoted and escaped JSON representation of `value`.
+fn string(value: &str) -> String {
+    serde_json::to_string(value).expect("Couldn't serialize string")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::core::types::U256;
+
+    fn signature() -> Signature {
+        "1df128dfe1f86df4e20ecc6ebbd586e0ab56e3fc8d0db9210422c3c765633ad8793af68aa232cf39cc3f75ea18f03260258f7276c2e0d555f98e1cf16672dd201c"
+            .parse()
+            .unwrap()
+    }
+
+    fn message(title: &str) -> Message {
+        Message {
+            title: String::from(title),
+            href: String::from("https://example.com"),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(1676559616),
+        }
+    }
+
+    #[test]
+    fn serializes_submission() {
+        let expected = include_str!("../tests/golden/submission.json");
+        assert_eq!(serialize(&message("hello world"), &signature()), expected.trim_end());
+    }
+
+    #[test]
+    fn serializes_vote() {
+        let expected = include_str!("../tests/golden/vote.json");
+        assert_eq!(serialize(&message(""), &signature()), expected.trim_end());
+    }
+
+    #[test]
+    fn escapes_like_json_stringify() {
+        let expected = include_str!("../tests/golden/escaped.json");
+        let title = "\"Quoted\" \\ back\tslash\n\u{1} – ünïcode / path";
+        assert_eq!(serialize(&message(title), &signature()), expected.trim_end());
+    }
+}
@@ -0,0 +1,124 @@
+// @format
+//! The messages Kiwi News nodes accept: their EIP-712 type, limits, normalization, canonical
+//! serialization and signing.
+use ethers::{
+    contract::{Eip712, EthAbiType},
+    core::types::{transaction::eip712::Eip712, Signature, U256},
+    signers::{LocalWallet, Signer},
+    utils::hex,
+};
+
+pub mod canonical;
+pub mod limits;
+pub mod normalize;
+
+/// How many hex characters of the digest make up a message's ID.
+pub const ID_LENGTH: usize = 12;
+
+// Define the EIP-712 message struct
+#[derive(Debug, Clone, Eip712, EthAbiType)]
+#[eip712(
+    name = "kiwinews",
+    version = "1.0.0",
+    salt = "kiwinews domain separator salt"
+)]
+pub struct Message {
+    pub title: String,
+    pub href: String,
+    pub r#type: String,
+    pub timestamp: U256,
+}
+
+impl Message {
+    /// Returns a short, stable ID to refer to the message locally: the beginning of its EIP-712
+    /// digest in hex.
+    pub fn id(&self) -> String {
+        let digest = self
+            .encode_eip712()
+            .expect("Couldn't compute the EIP-712 digest");
+        hex::encode(digest)[..ID_LENGTH].to_string()
+    }
+}
+
+/// Signs the given EIP-712 message with a `LocalWallet` instance.
+///
+/// Returns the generated signature.
+pub async fn sign(wallet: LocalWallet, message: &Message) -> Signature {
+    wallet
+        .sign_typed_data(message)
+        .await
+        .expect("Couldn't sign message")
+}
+
+// Unit test
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::{
+        core::{k256::ecdsa::SigningKey, types::H160},
+        signers::Wallet,
+    };
+
+    // Test for comparing signatures
+    #[tokio::test]
+    async fn compare_signatures() {
+        let timestamp = 1676559616;
+        let title = "hello world";
+        let href = "https://example.com";
+        let message = Message {
+            title: String::from(title),
+            href: String::from(href),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(timestamp),
+        };
+        dbg!(&message);
+
+        let wallet: Wallet<SigningKey> =
+            "ad54bdeade5537fb0a553190159783e45d02d316a992db05cbed606d3ca36b39"
+                .parse()
+                .unwrap();
+        let expected: H160 = "0x0f6A79A579658E401E0B81c6dde1F2cd51d97176"
+            .parse()
+            .unwrap();
+        assert_eq!(wallet.address(), expected);
+        let signature = sign(wallet, &message).await;
+        assert_eq!(signature.to_string(), "1df128dfe1f86df4e20ecc6ebbd586e0ab56e3fc8d0db9210422c3c765633ad8793af68aa232cf39cc3f75ea18f03260258f7276c2e0d555f98e1cf16672dd201c");
+    }
+
+    // Test that IDs are the beginning of the digest
+    #[test]
+    fn derives_ids_from_digests() {
+        let message = Message {
+            title: String::from("hello world"),
+            href: String::from("https://example.com"),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(1676559616),
+        };
+        let digest = hex::encode(message.encode_eip712().unwrap());
+        assert_eq!(message.id().len(), ID_LENGTH);
+        assert!(digest.starts_with(&message.id()));
+    }
+
+    // Test that piped line endings and stray whitespace don't change the signature
+    #[tokio::test]
+    async fn signatures_ignore_whitespace() {
+        let wallet: Wallet<SigningKey> =
+            "ad54bdeade5537fb0a553190159783e45d02d316a992db05cbed606d3ca36b39"
+                .parse()
+                .unwrap();
+        for (title, href) in [
+            ("hello world", "https://example.com"),
+            ("hello world\r\n", "https://example.com\n"),
+            ("  hello\t world ", " https://example.com"),
+        ] {
+            let message = Message {
+                title: normalize::title(title),
+                href: normalize::href(href),
+                r#type: String::from("amplify"),
+                timestamp: U256::from(1676559616),
+            };
+            let signature = sign(wallet.clone(), &message).await;
+            assert_eq!(signature.to_string(), "1df128dfe1f86df4e20ecc6ebbd586e0ab56e3fc8d0db9210422c3c765633ad8793af68aa232cf39cc3f75ea18f03260258f7276c2e0d555f98e1cf16672dd201c");
+        }
+    }
+}
@@ -0,0 +1,65 @@
+// @format
+
+/// The longest title the nodes accept, in characters.
+pub const MAX_TITLE_LENGTH: usize = 80;
+
+/// The longest href the nodes accept, in characters.
+pub const MAX_HREF_LENGTH: usize = 2048;
+
+/// Returns a description of every node limit the href or the title violates.
+pub fn violations(href: &str, title: &str) -> Vec<String> {
+    let mut violations = Vec::new();
+    let href_length = href.chars().count();
+    if href_length > MAX_HREF_LENGTH {
+        violations.push(format!(
+            "href is {} characters long, but nodes accept at most {}",
+            href_length, MAX_HREF_LENGTH
+        ));
+    }
+    let title_length = title.chars().count();
+    if title_length > MAX_TITLE_LENGTH {
+        violations.push(format!(
+            "title is {} characters long, but nodes accept at most {}",
+            title_length, MAX_TITLE_LENGTH
+        ));
+    }
+    violations
+}
+
+/// Shortens `title` to at most `max` characters, ending it with an ellipsis if it was cut.
+///
+/// No title fits into zero characters, so that returns an empty string.
+pub fn truncate(title: &str, max: usize) -> String {
+    if title.chars().count() <= max {
+        return title.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let kept: String = title.chars().take(max - 1).collect();
+    format!("{}…", kept.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncates_titles_with_ellipsis() {
+        assert_eq!(truncate("short", 10), "short");
+        assert_eq!(truncate("ünïcode titles", 9), "ünïcode…");
+        assert_eq!(truncate(&"a".repeat(100), MAX_TITLE_LENGTH).chars().count(), 80);
+        assert_eq!(truncate("short", 1), "…");
+        assert_eq!(truncate("short", 0), "");
+    }
+
+    #[test]
+    fn reports_violations() {
+        assert!(violations("https://example.com", "hello world").is_empty());
+        let title = "a".repeat(MAX_TITLE_LENGTH + 1);
+        assert_eq!(
+            violations("https://example.com", &title),
+            vec!["title is 81 characters long, but nodes accept at most 80"]
+        );
+    }
+}
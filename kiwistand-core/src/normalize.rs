@@ -0,0 +1,74 @@
+// @format
+
+/// The rules titles and hrefs are normalized by before signing, as shown by `inspect`.
+pub const RULES: &str = "titles have leading and trailing whitespace removed and every run of \
+whitespace, including line breaks and tabs, replaced by one space; hrefs have leading and \
+trailing whitespace removed; nothing else is changed, independent of locale and OS";
+
+/// Normalizes a title so the same input produces the same signature on every system.
+///
+/// Piped titles can end in `\r\n` on Windows or contain line breaks and tabs, which would
+/// otherwise become part of the signed message.
+pub fn title(title: &str) -> String {
+    title.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Normalizes an href by removing surrounding whitespace.
+pub fn href(href: &str) -> String {
+    href.trim().to_string()
+}
+
+/// Returns which rule the href or title break if they aren't normalized.
+pub fn problem(href_input: &str, title_input: &str) -> Option<String> {
+    if href(href_input) != href_input {
+        return Some(format!(
+            "The href {:?} has surrounding whitespace",
+            href_input
+        ));
+    }
+    if title(title_input) != title_input {
+        return Some(format!(
+            "The title {:?} has surrounding whitespace, line breaks, tabs or repeated spaces",
+            title_input
+        ));
+    }
+    None
+}
+
+/// Panics if the href or title aren't normalized, naming the rule they break.
+pub fn check(href_input: &str, title_input: &str) {
+    if let Some(problem) = problem(href_input, title_input) {
+        panic!("{}", problem);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_whitespace() {
+        assert_eq!(title("hello world"), "hello world");
+        assert_eq!(title("  hello world\r\n"), "hello world");
+        assert_eq!(title("hello\r\n\tworld \n"), "hello world");
+        assert_eq!(title(" \r\n"), "");
+        assert_eq!(href(" https://example.com\r\n"), "https://example.com");
+    }
+
+    #[test]
+    fn keeps_everything_else() {
+        assert_eq!(title("Ünïcode ǅ Title"), "Ünïcode ǅ Title");
+        assert_eq!(href("https://example.com/a%20b?x=Y"), "https://example.com/a%20b?x=Y");
+    }
+
+    #[test]
+    fn names_the_broken_rule() {
+        assert_eq!(problem("https://example.com", "hello world"), None);
+        assert!(problem(" https://example.com", "hello")
+            .unwrap()
+            .starts_with("The href"));
+        assert!(problem("https://example.com", "hello  world")
+            .unwrap()
+            .starts_with("The title"));
+    }
+}
@@ -0,0 +1,207 @@
+// @format
+use clap::{Args, Subcommand};
+use ethers::{
+    core::types::{transaction::eip712::Eip712, Signature},
+    utils::{hex, keccak256, to_checksum},
+};
+use kiwistand_core::Message;
+use serde_json::{json, Value};
+
+use std::fs::{self, OpenOptions};
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::Context;
+
+/// The hash the first entry of the audit log links to.
+const GENESIS: &str = "0x0000000000000000000000000000000000000000000000000000000000000000";
+
+/// `AuditArgs` contains the audit subcommand to run.
+#[derive(Args)]
+pub struct AuditArgs {
+    #[command(subcommand)]
+    command: AuditCommands,
+}
+
+/// The subcommands for the audit log of signatures.
+#[derive(Subcommand)]
+enum AuditCommands {
+    /// Checks that no entry of the audit log was changed, removed or inserted.
+    Verify,
+}
+
+/// Returns the path of the audit log, if there is a state directory.
+fn audit_path(context: &Context) -> Option<PathBuf> {
+    context.state_file("audit.jsonl")
+}
+
+/// Returns the hash of an entry's line, which the next entry links to.
+fn line_hash(line: &str) -> String {
+    format!("0x{}", hex::encode(keccak256(line.as_bytes())))
+}
+
+/// Appends an entry for a signature the CLI produced to the audit log.
+///
+/// Every entry records the message's ID and digest, the signer, whether it's a submission or a vote, the surface
+/// that requested it and the hash of the previous entry, so changing an entry breaks the chain.
+/// Nothing is logged in ephemeral mode without a state directory.
+pub fn record(context: &Context, message: &Message, signature: &Signature) {
+    let path = match audit_path(context) {
+        Some(path) => path,
+        None => return,
+    };
+    fs::create_dir_all(path.parent().unwrap()).expect("Couldn't create the state directory");
+    let mut file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(&path)
+        .expect("Couldn't open the audit log");
+    // Concurrent instances must not link two entries to the same predecessor.
+    file.lock().expect("Couldn't lock the audit log");
+    let mut contents = String::new();
+    file.read_to_string(&mut contents).expect("Couldn't read the audit log");
+    let previous = contents
+        .lines()
+        .last()
+        .map(line_hash)
+        .unwrap_or_else(|| String::from(GENESIS));
+
+    let digest = message
+        .encode_eip712()
+        .expect("Couldn't compute the EIP-712 digest");
+    let signer = signature.recover(digest).expect("Couldn't recover the signer");
+    let entry = json!({
+        "id": message.id(),
+        "timestamp": context.clock.now(),
+        "digest": format!("0x{}", hex::encode(digest)),
+        "signer": to_checksum(&signer, None),
+        "purpose": if message.title.is_empty() { "vote" } else { "submission" },
+        "surface": "cli",
+        "previous": previous,
+    });
+    writeln!(file, "{}", entry).expect("Couldn't write the audit log");
+    file.sync_all().expect("Couldn't write the audit log");
+}
+
+/// Returns how many submissions were signed at or after the Unix time `since`, or `None` if
+/// there's no audit log to count them in, i.e. in ephemeral mode without a state directory.
+pub fn submissions_since(context: &Context, since: u64) -> Option<usize> {
+    let contents = match audit_path(context).map(fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        Some(Err(_)) => return Some(0),
+        None => return None,
+    };
+    let count = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|entry| entry["purpose"] == "submission")
+        .filter(|entry| entry["timestamp"].as_u64().is_some_and(|time| time >= since))
+        .count();
+    Some(count)
+}
+
+/// Prints the entry of the signature whose ID, or digest, starts with `id`.
+///
+/// Returns false if no entry or more than one entry matches.
+pub fn show(context: &Context, id: &str) -> bool {
+    let prefix = format!("0x{}", id.trim_start_matches("0x").to_lowercase());
+    let contents = match audit_path(context).map(fs::read_to_string) {
+        Some(Ok(contents)) => contents,
+        _ => String::new(),
+    };
+    let entries: Vec<Value> = contents
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|entry| {
+            entry["digest"]
+                .as_str()
+                .is_some_and(|digest| digest.starts_with(&prefix))
+        })
+        .collect();
+    match entries.as_slice() {
+        [entry] => {
+            for field in ["id", "digest", "signer", "purpose", "surface", "timestamp"] {
+                let value = match &entry[field] {
+                    Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                };
+                println!("{:<10} {}", field, value);
+            }
+            true
+        }
+        [] => {
+            println!("problem:   no signature with the ID {} was logged", id);
+            false
+        }
+        entries => {
+            println!(
+                "problem:   {} signatures match {}, give more of the digest",
+                entries.len(),
+                id
+            );
+            false
+        }
+    }
+}
+
+/// Checks that every entry links to the one before it and returns the number of entries.
+///
+/// Entries removed from the end of the log can't be detected this way.
+fn verify(contents: &str) -> Result<usize, String> {
+    let mut previous = String::from(GENESIS);
+    let mut count = 0;
+    for (i, line) in contents.lines().enumerate() {
+        let entry: Value = serde_json::from_str(line)
+            .map_err(|error| format!("entry {} isn't valid JSON: {}", i + 1, error))?;
+        if entry["previous"] != previous.as_str() {
+            return Err(format!("entry {} doesn't link to the entry before it", i + 1));
+        }
+        previous = line_hash(line);
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Runs an audit subcommand and returns false if a problem was found.
+pub fn run(context: &Context, args: &AuditArgs) -> bool {
+    match &args.command {
+        AuditCommands::Verify => {
+            let path = audit_path(context).unwrap_or_else(|| {
+                fail!("the audit log needs a state directory, see --state-dir")
+            });
+            let contents = if path.exists() {
+                fs::read_to_string(&path).expect("Couldn't read the audit log")
+            } else {
+                String::new()
+            };
+            match verify(&contents) {
+                Ok(count) => {
+                    println!("{} entries, the chain is intact", count);
+                    true
+                }
+                Err(problem) => {
+                    println!("problem:   {}", problem);
+                    false
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_tampering() {
+        let first = json!({ "digest": "0x01", "previous": GENESIS }).to_string();
+        let second = json!({ "digest": "0x02", "previous": line_hash(&first) }).to_string();
+        assert_eq!(verify(&format!("{}\n{}\n", first, second)), Ok(2));
+        assert_eq!(verify(""), Ok(0));
+
+        let changed = first.replace("0x01", "0x03");
+        assert!(verify(&format!("{}\n{}\n", changed, second)).is_err());
+        assert!(verify(&format!("{}\n", second)).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+// @format
+use clap::{Args, Subcommand};
+use kiwistand_api::atomic;
+use serde_json::{json, Map, Value};
+
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use crate::{confirm, lock, Context};
+
+/// The state files that are backed up. The keystore isn't, so that no further copies of the
+/// key are made.
+const FILES: [&str; 6] = [
+    "templates.json",
+    "accounts.json",
+    "drafts.json",
+    "policy.toml",
+    "bridge-github.json",
+    "audit.jsonl",
+];
+
+/// The hash-chained audit log, which is backed up but never restored: it's only ever appended
+/// to, and dropping its tail couldn't be detected.
+const AUDIT_LOG: &str = "audit.jsonl";
+
+/// How many backups are kept unless told otherwise.
+const DEFAULT_KEEP: usize = 7;
+
+/// How old, in seconds, the latest backup may be before `--backup-daily` takes another one.
+const DAILY: u64 = 24 * 60 * 60;
+
+/// `BackupArgs` contains the backup subcommand to run.
+#[derive(Args)]
+pub struct BackupArgs {
+    #[command(subcommand)]
+    command: BackupCommands,
+}
+
+/// The subcommands for backing up and restoring the state files.
+#[derive(Subcommand)]
+enum BackupCommands {
+    /// Backs up the state files now.
+    Now {
+        /// How many of the newest backups to keep.
+        #[arg(long, default_value_t = DEFAULT_KEEP)]
+        keep: usize,
+    },
+    /// Lists the backups, oldest first.
+    List,
+    /// Replaces the state files with those from a backup.
+    Restore { name: String },
+}
+
+/// Returns the directory holding the backups.
+fn backups_dir(context: &Context) -> PathBuf {
+    context
+        .state_file("backups")
+        .unwrap_or_else(|| fail!("backups need a state directory, see --state-dir"))
+}
+
+/// Returns the names of all backups, oldest first.
+fn list(context: &Context) -> Vec<String> {
+    let mut names: Vec<String> = match fs::read_dir(backups_dir(context)) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("backup-") && name.ends_with(".json"))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    names.sort_by_key(|name| order(name));
+    names
+}
+
+/// Returns the Unix time a backup was taken at and its number within that second, from its name.
+///
+/// Backups are named `backup-<time>.json`, or `backup-<time>-<number>.json` if there already was
+/// one in the same second.
+fn order(name: &str) -> (u64, u64) {
+    let stem = name.trim_start_matches("backup-").trim_end_matches(".json");
+    let (time, number) = stem.split_once('-').unwrap_or((stem, "0"));
+    (time.parse().unwrap_or_default(), number.parse().unwrap_or_default())
+}
+
+/// Returns the Unix time a backup was taken at, from its name.
+fn timestamp(name: &str) -> u64 {
+    order(name).0
+}
+
+/// Returns the backups to delete so that only the newest `keep` remain.
+fn expired(names: &[String], keep: usize) -> &[String] {
+    &names[..names.len().saturating_sub(keep)]
+}
+
+/// Writes the existing state files into a new backup and deletes all but the newest `keep`.
+///
+/// The caller must hold the lock on the state directory, so no file changes meanwhile.
+fn backup(context: &Context, keep: usize) -> io::Result<PathBuf> {
+    let mut files = Map::new();
+    for name in FILES {
+        let path = context.state_file(name).unwrap();
+        if let Ok(contents) = fs::read_to_string(path) {
+            files.insert(name.to_string(), Value::String(contents));
+        }
+    }
+    let dir = backups_dir(context);
+    let now = context.clock.now();
+    let mut path = dir.join(format!("backup-{}.json", now));
+    let mut number = 0;
+    while path.exists() {
+        number += 1;
+        path = dir.join(format!("backup-{}-{}.json", now, number));
+    }
+    let contents = serde_json::to_string_pretty(&json!({ "files": files })).unwrap();
+    atomic::write(&path, contents)?;
+
+    for name in expired(&list(context), keep) {
+        fs::remove_file(dir.join(name))?;
+    }
+    Ok(path)
+}
+
+/// Backs up the state files if `--backup-daily` was given and the latest backup is a day old.
+///
+/// Failing to back up only prints a warning, so it doesn't stop the command itself.
+pub fn auto(context: &Context) {
+    if !context.backup_daily || context.state_dir().is_none() {
+        return;
+    }
+    let latest = list(context).last().map(|name| timestamp(name));
+    if latest.is_some_and(|latest| context.clock.now() < latest + DAILY) {
+        return;
+    }
+    let _lock = match lock::try_lock_state(context) {
+        Some(lock) => lock,
+        None => {
+            eprintln!("Warning: skipped the daily backup, another instance is running");
+            return;
+        }
+    };
+    if let Err(error) = backup(context, DEFAULT_KEEP) {
+        eprintln!("Warning: the daily backup failed: {}", error);
+    }
+}
+
+/// Runs a backup subcommand.
+pub fn run(context: &Context, args: &BackupArgs) {
+    match &args.command {
+        BackupCommands::Now { keep } => {
+            let _lock = lock::lock_state(context);
+            let path = backup(context, *keep).expect("Couldn't write the backup");
+            println!("Backed up to {}", path.display());
+        }
+        BackupCommands::List => {
+            for name in list(context) {
+                println!("{}", name);
+            }
+        }
+        BackupCommands::Restore { name } => restore(context, name),
+    }
+}
+
+/// Replaces the state files with those from the backup `name`, after the user confirmed it.
+///
+/// State files that aren't in the backup are removed, so the state is exactly the one that was
+/// backed up. The audit log is the exception and kept as it is. The current state is backed up
+/// first, so the restore can be undone.
+fn restore(context: &Context, name: &str) {
+    let _lock = lock::lock_state(context);
+    let path = backups_dir(context).join(name);
+    let contents = fs::read_to_string(&path)
+        .unwrap_or_else(|_| fail!("there is no backup {}", path.display()));
+    let backed_up: Value = serde_json::from_str(&contents).expect("Couldn't parse the backup");
+    let files = backed_up["files"].as_object().expect("Backup without files");
+    if let Some(file) = files.keys().find(|file| !FILES.contains(&file.as_str())) {
+        fail!("The backup contains the unexpected file {}", file);
+    }
+    let restored = files.keys().filter(|file| *file != AUDIT_LOG).count();
+    let question = format!(
+        "Replace the state files with the {} from {}, removing the others?",
+        restored, name
+    );
+    if !confirm(context, &question) {
+        return;
+    }
+    let current = backup(context, usize::MAX).expect("Couldn't back up the current state");
+    println!("Backed up the current state to {}", current.display());
+
+    for file in FILES {
+        let path = context.state_file(file).unwrap();
+        if file == AUDIT_LOG {
+            println!("Kept {}, the audit log is only ever appended to", path.display());
+            continue;
+        }
+        match files.get(file) {
+            Some(contents) => {
+                atomic::write(&path, contents.as_str().unwrap_or_default())
+                    .expect("Couldn't restore a state file");
+                println!("Restored {}", path.display());
+            }
+            None if path.exists() => {
+                fs::remove_file(&path).expect("Couldn't remove a state file");
+                println!("Removed {}", path.display());
+            }
+            None => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::{test_context, test_dir};
+
+    #[test]
+    fn keeps_newest_backups() {
+        let names: Vec<String> = [1, 2, 3].iter().map(|t| format!("backup-{}.json", t)).collect();
+        assert_eq!(expired(&names, 2), &names[..1]);
+        assert!(expired(&names, 7).is_empty());
+        assert_eq!(timestamp("backup-1700000000.json"), 1700000000);
+        assert_eq!(order("backup-1700000000-2.json"), (1700000000, 2));
+        let mut names = vec!["backup-2-1.json", "backup-10.json", "backup-2.json"];
+        names.sort_by_key(|name| order(name));
+        assert_eq!(names, ["backup-2.json", "backup-2-1.json", "backup-10.json"]);
+    }
+
+    #[test]
+    fn backs_up_once_a_day() {
+        let dir = test_dir("backup-daily");
+        let mut context = test_context(&["--state-dir", dir.to_str().unwrap(), "--backup-daily"]);
+        let start = 1_700_000_000;
+        context.clock = Box::new(FixedClock(start));
+        auto(&context);
+        context.clock = Box::new(FixedClock(start + DAILY - 1));
+        auto(&context);
+        assert_eq!(list(&context).len(), 1);
+
+        context.clock = Box::new(FixedClock(start + DAILY));
+        auto(&context);
+        assert_eq!(list(&context).len(), 2);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn restores_exactly_the_backed_up_state() {
+        let dir = test_dir("backup-restore");
+        let mut context = test_context(&["--state-dir", dir.to_str().unwrap(), "--yes"]);
+        context.clock = Box::new(FixedClock(1_700_000_000));
+        fs::write(dir.join("templates.json"), "{\"release\":\"{title}\"}").unwrap();
+        let backup_path = backup(&context, DEFAULT_KEEP).unwrap();
+        let name = backup_path.file_name().unwrap().to_str().unwrap().to_string();
+
+        fs::write(dir.join("templates.json"), "{}").unwrap();
+        fs::write(dir.join("drafts.json"), "[]").unwrap();
+        fs::write(dir.join("audit.jsonl"), "{}\n").unwrap();
+        // Restoring within the same second doesn't overwrite the backup being restored.
+        restore(&context, &name);
+        let templates = fs::read_to_string(dir.join("templates.json")).unwrap();
+        assert_eq!(templates, "{\"release\":\"{title}\"}");
+        assert!(!dir.join("drafts.json").exists());
+        assert_eq!(fs::read_to_string(dir.join("audit.jsonl")).unwrap(), "{}\n");
+        // The state before restoring was backed up too.
+        assert_eq!(list(&context), [name, String::from("backup-1700000000-1.json")]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
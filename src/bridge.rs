@@ -0,0 +1,195 @@
+// @format
+use clap::{Args, Subcommand};
+use kiwistand_api::atomic;
+use serde_json::Value;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{create_message, keystore_password, lock, send, Context};
+
+/// `BridgeArgs` contains the bridge to run.
+#[derive(Args)]
+pub struct BridgeArgs {
+    #[command(subcommand)]
+    command: BridgeCommands,
+}
+
+/// The services that can be bridged to Kiwi News.
+#[derive(Subcommand)]
+enum BridgeCommands {
+    /// Submits the releases of a GitHub repository.
+    Github(GithubArgs),
+}
+
+/// `GithubArgs` contains the keystore password and the repository whose releases to announce.
+#[derive(Args)]
+struct GithubArgs {
+    /// The keystore password, unless --password-command or --password-file provides it.
+    #[arg(long)]
+    password: Option<String>,
+    /// The repository, as `owner/name`.
+    #[arg(long)]
+    repo: String,
+    /// Print the submissions instead of signing and sending them.
+    #[arg(long)]
+    dry_run: bool,
+    /// Keep watching, checking for new releases every given number of seconds.
+    #[arg(long)]
+    interval: Option<u64>,
+}
+
+/// A published release of a GitHub repository.
+struct Release {
+    id: u64,
+    tag: String,
+    name: String,
+    url: String,
+}
+
+/// Runs a bridge.
+pub async fn run(context: &Context, args: &BridgeArgs) {
+    match &args.command {
+        BridgeCommands::Github(args) => {
+            // Resolved once, so a password command isn't run again for every release.
+            let password = if args.dry_run {
+                String::new()
+            } else {
+                keystore_password(context, &args.password)
+            };
+            loop {
+                announce_releases(context, args, &password).await;
+                match args.interval {
+                    Some(interval) => tokio::time::sleep(Duration::from_secs(interval)).await,
+                    None => break,
+                }
+            }
+        }
+    }
+}
+
+/// Submits every release published since the last run, oldest first.
+///
+/// The ID of the last announced release is stored per repository, so each release is only
+/// submitted once. On the first run only the latest release is submitted. The state directory
+/// stays locked meanwhile, so overlapping runs can't announce a release twice.
+async fn announce_releases(context: &Context, args: &GithubArgs, password: &String) {
+    let _lock = (!args.dry_run).then(|| lock::lock_state(context));
+    let releases = fetch_releases(context, &args.repo).await;
+    let mut cursors = read_cursors(context);
+    let pending: Vec<&Release> = match cursors.get(&args.repo) {
+        Some(cursor) => releases.iter().filter(|release| release.id > *cursor).collect(),
+        None => releases.iter().take(1).collect(),
+    };
+
+    for release in pending.into_iter().rev() {
+        let title = release_title(&args.repo, release);
+        if args.dry_run {
+            println!("{} {:?}", release.url, title);
+            continue;
+        }
+        let message = create_message(context, password, &release.url, &title, false, None).await;
+        let message = match message {
+            Some(message) => message,
+            None => break,
+        };
+        if !send(context, message).await {
+            eprintln!("Stopping, the release {} wasn't accepted", release.tag);
+            break;
+        }
+        cursors.insert(args.repo.clone(), release.id);
+        write_cursors(context, &cursors);
+    }
+}
+
+/// Fetches the published releases of `repo`, newest first.
+async fn fetch_releases(context: &Context, repo: &str) -> Vec<Release> {
+    let url = format!("https://api.github.com/repos/{}/releases", repo);
+    let response = context
+        .transport
+        .get(&url)
+        .await
+        .expect("Failed fetching the releases");
+    if !response.is_success() {
+        fail!("GitHub responded with {} for {}", response.status, repo);
+    }
+    let releases: Vec<Value> =
+        serde_json::from_str(&response.body).expect("Couldn't parse the releases");
+    releases
+        .iter()
+        .filter(|release| release["draft"] != true && release["prerelease"] != true)
+        .map(|release| Release {
+            id: release["id"].as_u64().expect("Release without an ID"),
+            tag: release["tag_name"].as_str().unwrap_or_default().to_string(),
+            name: release["name"].as_str().unwrap_or_default().to_string(),
+            url: release["html_url"]
+                .as_str()
+                .expect("Release without a URL")
+                .to_string(),
+        })
+        .collect()
+}
+
+/// Returns the title announcing `release`, e.g. "kiwistand-cli v0.2.0: Faster signing".
+fn release_title(repo: &str, release: &Release) -> String {
+    let project = repo.rsplit('/').next().unwrap_or(repo);
+    let name = release.name.trim();
+    if name.is_empty() || name == release.tag {
+        format!("{} {} released", project, release.tag)
+    } else {
+        format!("{} {}: {}", project, release.tag, name)
+    }
+}
+
+/// Returns the path of the file storing the last announced release per repository.
+fn cursors_path(context: &Context) -> PathBuf {
+    context
+        .state_file("bridge-github.json")
+        .unwrap_or_else(|| fail!("the bridge needs a state directory, see --state-dir"))
+}
+
+/// Reads the ID of the last announced release per repository.
+fn read_cursors(context: &Context) -> BTreeMap<String, u64> {
+    let path = cursors_path(context);
+    if !path.exists() {
+        return BTreeMap::new();
+    }
+    let contents = fs::read_to_string(path).expect("Couldn't read the bridge cursors");
+    serde_json::from_str(&contents).expect("Couldn't parse the bridge cursors")
+}
+
+/// Stores the ID of the last announced release per repository.
+fn write_cursors(context: &Context, cursors: &BTreeMap<String, u64>) {
+    let path = cursors_path(context);
+    let contents = serde_json::to_string_pretty(cursors).unwrap();
+    atomic::write(&path, contents).expect("Couldn't write the bridge cursors");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(tag: &str, name: &str) -> Release {
+        Release {
+            id: 1,
+            tag: String::from(tag),
+            name: String::from(name),
+            url: String::from("https://github.com/attestate/kiwistand-cli/releases/tag/v0.2.0"),
+        }
+    }
+
+    #[test]
+    fn titles_releases() {
+        let repo = "attestate/kiwistand-cli";
+        assert_eq!(
+            release_title(repo, &release("v0.2.0", "Faster signing")),
+            "kiwistand-cli v0.2.0: Faster signing"
+        );
+        assert_eq!(
+            release_title(repo, &release("v0.2.0", "v0.2.0")),
+            "kiwistand-cli v0.2.0 released"
+        );
+    }
+}
@@ -0,0 +1,29 @@
+// @format
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A source of the current Unix time in seconds.
+pub trait Clock {
+    fn now(&self) -> u64;
+}
+
+/// `SystemClock` reads the time from the system.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        let start = SystemTime::now();
+        let now = start
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards");
+        now.as_secs()
+    }
+}
+
+/// `FixedClock` always returns the same time, for tests and `--fake-time`.
+pub struct FixedClock(pub u64);
+
+impl Clock for FixedClock {
+    fn now(&self) -> u64 {
+        self.0
+    }
+}
@@ -0,0 +1,183 @@
+// @format
+use clap::{Args, Subcommand};
+use ethers::{
+    core::types::{transaction::eip712::Eip712, Signature, U256},
+    utils::{hex, to_checksum},
+};
+use kiwistand_core::{normalize, sign, Message};
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{read_key, read_stdin, secret, Context};
+
+/// `CompatArgs` contains the compatibility check to run.
+#[derive(Args)]
+pub struct CompatArgs {
+    #[command(subcommand)]
+    command: CompatCommands,
+}
+
+/// The checks against payloads produced by other clients.
+#[derive(Subcommand)]
+enum CompatCommands {
+    /// Compares a payload captured from the JS client with the one this CLI builds.
+    Diff(DiffArgs),
+}
+
+/// `DiffArgs` contains the captured payload and the inputs it was built from.
+#[derive(Args)]
+struct DiffArgs {
+    /// Path of the payload the JS client sent, or `-` to read it from stdin.
+    #[arg(long)]
+    js_payload: PathBuf,
+    /// The link the payload was built from.
+    #[arg(long)]
+    url: String,
+    /// The title the payload was built from, empty for votes.
+    #[arg(long, default_value = "")]
+    title: String,
+    /// The timestamp of the payload, in Unix time.
+    #[arg(long)]
+    timestamp: u64,
+    /// Also sign with the keystore and compare the signatures, if it holds the JS client's key.
+    #[arg(long)]
+    password: Option<String>,
+}
+
+/// Runs a compatibility check and returns false if anything differs.
+pub async fn run(context: &Context, args: &CompatArgs) -> bool {
+    match &args.command {
+        CompatCommands::Diff(args) => diff(context, args).await,
+    }
+}
+
+/// Prints the comparison of a field and returns whether both sides are equal.
+fn compare(label: &str, js: &str, rust: &str) -> bool {
+    if js == rust {
+        println!("{:<10} same     {}", label, rust);
+        true
+    } else {
+        println!("{:<10} differs\n           js:      {}\n           rust:    {}", label, js, rust);
+        false
+    }
+}
+
+/// Builds the message the CLI would sign from the given inputs and compares it field by field,
+/// by digest and optionally by signature with the JS client's payload.
+async fn diff(context: &Context, args: &DiffArgs) -> bool {
+    let contents = if args.js_payload == Path::new("-") {
+        read_stdin()
+    } else {
+        fs::read_to_string(&args.js_payload).expect("Couldn't read the JS payload")
+    };
+    let js: Value = serde_json::from_str(&contents).expect("Couldn't parse the JS payload");
+    let message = Message {
+        title: normalize::title(&args.title),
+        href: normalize::href(&args.url),
+        r#type: String::from("amplify"),
+        timestamp: U256::from(args.timestamp),
+    };
+    let rust = json!({
+        "title": message.title,
+        "href": message.href,
+        "type": message.r#type,
+        "timestamp": args.timestamp,
+    });
+
+    let mut same = true;
+    for field in ["title", "href", "type", "timestamp"] {
+        same &= compare(field, &js[field].to_string(), &rust[field].to_string());
+    }
+
+    let digest = message
+        .encode_eip712()
+        .expect("Couldn't compute the EIP-712 digest");
+    let js_digest = match (
+        js["title"].as_str(),
+        js["href"].as_str(),
+        js["type"].as_str(),
+        js["timestamp"].as_u64(),
+    ) {
+        (Some(title), Some(href), Some(r#type), Some(timestamp)) => Message {
+            title: title.to_string(),
+            href: href.to_string(),
+            r#type: r#type.to_string(),
+            timestamp: U256::from(timestamp),
+        }
+        .encode_eip712()
+        .map(|digest| format!("0x{}", hex::encode(digest)))
+        .unwrap_or_default(),
+        _ => String::from("(fields are malformed)"),
+    };
+    same &= compare("digest", &js_digest, &format!("0x{}", hex::encode(digest)));
+
+    let js_signature = js["signature"].as_str().unwrap_or_default();
+    if let Ok(signature) = js_signature.parse::<Signature>() {
+        if let Ok(signer) = signature.recover(digest) {
+            println!("{:<10} {}", "signer", to_checksum(&signer, None));
+        }
+    }
+    match args.password.clone().or_else(|| secret::password(context)) {
+        Some(password) => {
+            let wallet = read_key(&context.keystore(), &password);
+            let signature = format!("0x{}", sign(wallet, &message).await);
+            same &= compare("signature", js_signature, &signature);
+        }
+        None => println!("{:<10} pass --password to compare", "signature"),
+    }
+    same
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_context, test_dir};
+    use ethers::signers::LocalWallet;
+    use kiwistand_core::canonical;
+
+    const KEY: &str = "ad54bdeade5537fb0a553190159783e45d02d316a992db05cbed606d3ca36b39";
+
+    fn diff_args(js_payload: PathBuf, title: &str, password: Option<&str>) -> DiffArgs {
+        DiffArgs {
+            js_payload,
+            url: String::from("https://example.com"),
+            title: String::from(title),
+            timestamp: 1676559616,
+            password: password.map(String::from),
+        }
+    }
+
+    #[test]
+    fn compares_fields() {
+        assert!(compare("title", "hello", "hello"));
+        assert!(!compare("title", "hello", "hello world"));
+    }
+
+    #[tokio::test]
+    async fn accepts_matching_payloads_and_rejects_others() {
+        let dir = test_dir("compat-diff");
+        let key = hex::decode(KEY).unwrap();
+        eth_keystore::encrypt_key(&dir, &mut rand::thread_rng(), &key, "secret", Some("key"))
+            .unwrap();
+        let context = test_context(&["--state-dir", dir.to_str().unwrap()]);
+
+        let message = Message {
+            title: String::from("hello world"),
+            href: String::from("https://example.com"),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(1676559616),
+        };
+        let wallet: LocalWallet = KEY.parse().unwrap();
+        let signature = sign(wallet, &message).await;
+        let payload = dir.join("payload.json");
+        fs::write(&payload, canonical::serialize(&message, &signature)).unwrap();
+
+        assert!(diff(&context, &diff_args(payload.clone(), "hello world", None)).await);
+        let with_signature = diff_args(payload.clone(), "hello world", Some("secret"));
+        assert!(diff(&context, &with_signature).await);
+        assert!(!diff(&context, &diff_args(payload, "goodbye world", None)).await);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,472 @@
+// @format
+use clap::{Args, Subcommand};
+use ethers::utils::{hex, keccak256};
+use kiwistand_api::atomic;
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{create_message, keystore_password, lock, prompt, send, Context};
+
+/// `DraftArgs` contains the draft subcommand to run.
+#[derive(Args)]
+pub struct DraftArgs {
+    #[command(subcommand)]
+    command: DraftCommands,
+}
+
+/// The subcommands for managing unsubmitted submissions.
+#[derive(Subcommand)]
+enum DraftCommands {
+    /// Saves a submission to review and submit later.
+    Save {
+        href: String,
+        title: String,
+        /// A private note, which is never sent.
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Lists all drafts.
+    List,
+    /// Changes a draft, asking for every field when none is given.
+    Edit {
+        id: u64,
+        #[arg(long)]
+        href: Option<String>,
+        #[arg(long)]
+        title: Option<String>,
+        #[arg(long)]
+        note: Option<String>,
+    },
+    /// Signs and sends a draft, and removes it once the node accepted it.
+    Submit(SubmitDraftArgs),
+    /// Removes a draft without submitting it.
+    Remove { id: u64 },
+    /// Writes every draft to its own file in a directory, e.g. a git repository shared by a team.
+    ExportDir {
+        dir: PathBuf,
+        /// Replace shared drafts whose fields conflict with the local ones.
+        #[arg(long)]
+        overwrite: bool,
+    },
+    /// Adds the drafts from the files in a directory, merging those of links already drafted.
+    ImportDir { dir: PathBuf },
+}
+
+/// `SubmitDraftArgs` contains the draft to submit and how to sign it.
+#[derive(Args)]
+struct SubmitDraftArgs {
+    id: u64,
+    /// The keystore password, unless --password-command or --password-file provides it.
+    #[arg(long)]
+    password: Option<String>,
+    /// Sign with a Ledger instead of the keystore.
+    #[arg(long)]
+    ledger: bool,
+    /// The Ledger address index to sign with.
+    #[arg(long, requires = "ledger")]
+    address_index: Option<usize>,
+}
+
+/// A submission saved for later.
+#[derive(Clone)]
+struct Draft {
+    id: u64,
+    href: String,
+    title: String,
+    note: String,
+}
+
+impl Draft {
+    fn from_json(value: &Value) -> Draft {
+        Draft {
+            id: value["id"].as_u64().expect("Draft without id"),
+            href: value["href"].as_str().unwrap_or_default().to_string(),
+            title: value["title"].as_str().unwrap_or_default().to_string(),
+            note: value["note"].as_str().unwrap_or_default().to_string(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        json!({ "id": self.id, "href": self.href, "title": self.title, "note": self.note })
+    }
+
+    /// Parses a shared draft, which has no ID.
+    fn from_toml(contents: &str, id: u64) -> Result<Draft, toml::de::Error> {
+        let table: toml::Table = contents.parse()?;
+        let field = |name: &str| {
+            let value = table.get(name).and_then(|value| value.as_str());
+            value.unwrap_or_default().to_string()
+        };
+        Ok(Draft {
+            id,
+            href: field("href"),
+            title: field("title"),
+            note: field("note"),
+        })
+    }
+
+    /// Serializes the draft to share it, without its ID, as that is local.
+    fn to_toml(&self) -> String {
+        let mut table = toml::Table::new();
+        table.insert(String::from("href"), self.href.clone().into());
+        table.insert(String::from("title"), self.title.clone().into());
+        table.insert(String::from("note"), self.note.clone().into());
+        toml::to_string(&table).unwrap()
+    }
+
+    /// Fills the empty fields of the draft with those of `theirs`, a draft of the same link.
+    ///
+    /// Returns the names of the fields both drafts set to different values, which are kept.
+    fn merge(&mut self, theirs: &Draft) -> Vec<&'static str> {
+        let mut conflicts = Vec::new();
+        for (name, ours, theirs) in [
+            ("title", &mut self.title, &theirs.title),
+            ("note", &mut self.note, &theirs.note),
+        ] {
+            if ours.is_empty() {
+                *ours = theirs.clone();
+            } else if !theirs.is_empty() && ours != theirs {
+                conflicts.push(name);
+            }
+        }
+        conflicts
+    }
+}
+
+/// Returns the path of the file storing the drafts.
+fn drafts_path(context: &Context) -> PathBuf {
+    context
+        .state_file("drafts.json")
+        .unwrap_or_else(|| fail!("drafts need a state directory, see --state-dir"))
+}
+
+/// Reads all drafts, in the order they were saved.
+fn read_drafts(context: &Context) -> Vec<Draft> {
+    let path = drafts_path(context);
+    if !path.exists() {
+        return Vec::new();
+    }
+    let contents = fs::read_to_string(path).expect("Couldn't read the drafts");
+    let drafts: Vec<Value> = serde_json::from_str(&contents).expect("Couldn't parse the drafts");
+    drafts.iter().map(Draft::from_json).collect()
+}
+
+/// Stores all drafts, replacing the previous ones.
+fn write_drafts(context: &Context, drafts: &[Draft]) {
+    let drafts: Vec<Value> = drafts.iter().map(Draft::to_json).collect();
+    let contents = serde_json::to_string_pretty(&drafts).unwrap();
+    atomic::write(&drafts_path(context), contents).expect("Couldn't write the drafts");
+}
+
+/// Returns the name of the file a draft is shared as.
+///
+/// The name is derived from the link, so teammates drafting different links never write the
+/// same file, while drafts of the same link end up in one.
+fn shared_name(href: &str) -> String {
+    format!("draft-{}.toml", &hex::encode(keccak256(href.as_bytes()))[..16])
+}
+
+/// Reads the shared draft at `path`, panicking if it isn't valid TOML.
+fn read_shared(path: &Path) -> Draft {
+    let contents = fs::read_to_string(path).expect("Couldn't read the shared draft");
+    Draft::from_toml(&contents, 0)
+        .unwrap_or_else(|error| fail!("Couldn't parse {}: {}", path.display(), error))
+}
+
+/// Writes every draft to its own TOML file in `dir`.
+///
+/// A file another curator already wrote for the same link is merged with the local draft. If
+/// they set a field to a different value, the file is left alone and the conflict reported,
+/// unless `overwrite` is true.
+fn export_dir(drafts: &[Draft], dir: &Path, overwrite: bool) {
+    for draft in drafts {
+        let path = dir.join(shared_name(&draft.href));
+        let mut shared = Draft { id: 0, ..draft.clone() };
+        if path.exists() && !overwrite {
+            let conflicts = shared.merge(&read_shared(&path));
+            if !conflicts.is_empty() {
+                eprintln!(
+                    "Skipped {}, its {} differs from draft {}; import it or pass --overwrite",
+                    path.display(),
+                    conflicts.join(" and "),
+                    draft.id
+                );
+                continue;
+            }
+        }
+        atomic::write(&path, shared.to_toml()).expect("Couldn't write the shared draft");
+        println!("{}", path.display());
+    }
+}
+
+/// Adds the drafts from the TOML files in `dir`.
+///
+/// A shared draft of a link that's already drafted is merged into the local draft, keeping the
+/// local value of every field they set differently and reporting it.
+fn import_dir(drafts: &mut Vec<Draft>, dir: &Path) {
+    let mut paths: Vec<PathBuf> = fs::read_dir(dir)
+        .expect("Couldn't read the drafts directory")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|extension| extension == "toml"))
+        .collect();
+    paths.sort();
+    for path in paths {
+        let id = drafts.iter().map(|draft| draft.id).max().unwrap_or(0) + 1;
+        let mut theirs = read_shared(&path);
+        if theirs.href.is_empty() {
+            continue;
+        }
+        match drafts.iter_mut().find(|known| known.href == theirs.href) {
+            Some(draft) => {
+                for field in draft.merge(&theirs) {
+                    eprintln!(
+                        "Kept the {} of draft {}, {} has another one",
+                        field,
+                        draft.id,
+                        path.display()
+                    );
+                }
+            }
+            None => {
+                theirs.id = id;
+                println!("Imported draft {} from {}", id, path.display());
+                drafts.push(theirs);
+            }
+        }
+    }
+}
+
+/// Returns the position of the draft with the given ID, panicking if there is none.
+fn position(drafts: &[Draft], id: u64) -> usize {
+    drafts
+        .iter()
+        .position(|draft| draft.id == id)
+        .unwrap_or_else(|| fail!("there is no draft {}", id))
+}
+
+/// Asks for a new value of a field, keeping the current one if the answer is empty.
+fn ask(label: &str, current: &str) -> String {
+    let answer = prompt(&format!("{} [{}]:", label, current));
+    if answer.trim().is_empty() {
+        current.to_string()
+    } else {
+        answer.trim().to_string()
+    }
+}
+
+/// Runs a draft subcommand.
+pub async fn run(context: &Context, args: &DraftArgs) {
+    let _lock = lock::lock_state(context);
+    let mut drafts = read_drafts(context);
+    match &args.command {
+        DraftCommands::Save { href, title, note } => {
+            let id = drafts.iter().map(|draft| draft.id).max().unwrap_or(0) + 1;
+            drafts.push(Draft {
+                id,
+                href: href.clone(),
+                title: title.clone(),
+                note: note.clone().unwrap_or_default(),
+            });
+            write_drafts(context, &drafts);
+            println!("Saved draft {}", id);
+        }
+        DraftCommands::List => {
+            for draft in &drafts {
+                println!("{:>3}. {}\n     {}", draft.id, draft.title, draft.href);
+                if !draft.note.is_empty() {
+                    println!("     note: {}", draft.note);
+                }
+            }
+        }
+        DraftCommands::Edit {
+            id,
+            href,
+            title,
+            note,
+        } => {
+            let index = position(&drafts, *id);
+            let draft = &mut drafts[index];
+            if href.is_none() && title.is_none() && note.is_none() {
+                draft.href = ask("Link", &draft.href);
+                draft.title = ask("Title", &draft.title);
+                draft.note = ask("Note", &draft.note);
+            } else {
+                draft.href = href.clone().unwrap_or_else(|| draft.href.clone());
+                draft.title = title.clone().unwrap_or_else(|| draft.title.clone());
+                draft.note = note.clone().unwrap_or_else(|| draft.note.clone());
+            }
+            write_drafts(context, &drafts);
+        }
+        DraftCommands::Submit(args) => {
+            let index = position(&drafts, args.id);
+            let password = if args.ledger {
+                String::new()
+            } else {
+                keystore_password(context, &args.password)
+            };
+            let draft = &drafts[index];
+            let message = create_message(
+                context,
+                &password,
+                &draft.href,
+                &draft.title,
+                args.ledger,
+                args.address_index,
+            )
+            .await;
+            if let Some(message) = message {
+                if send(context, message).await {
+                    drafts.remove(index);
+                    write_drafts(context, &drafts);
+                }
+            }
+        }
+        DraftCommands::Remove { id } => {
+            drafts.remove(position(&drafts, *id));
+            write_drafts(context, &drafts);
+        }
+        DraftCommands::ExportDir { dir, overwrite } => export_dir(&drafts, dir, *overwrite),
+        DraftCommands::ImportDir { dir } => {
+            import_dir(&mut drafts, dir);
+            write_drafts(context, &drafts);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{test_context, test_dir};
+    use ethers::signers::LocalWallet;
+
+    fn save(href: &str, title: &str) -> DraftArgs {
+        let save = DraftCommands::Save {
+            href: href.to_string(),
+            title: title.to_string(),
+            note: None,
+        };
+        DraftArgs { command: save }
+    }
+
+    #[tokio::test]
+    async fn saves_edits_and_removes_drafts() {
+        let dir = test_dir("draft-store");
+        let context = test_context(&["--state-dir", dir.to_str().unwrap()]);
+        run(&context, &save("https://example.com", "Example")).await;
+        run(&context, &save("https://example.org", "Other")).await;
+        let edit = DraftCommands::Edit {
+            id: 1,
+            href: None,
+            title: Some(String::from("Edited")),
+            note: Some(String::from("check the link")),
+        };
+        run(&context, &DraftArgs { command: edit }).await;
+        let drafts = read_drafts(&context);
+        assert_eq!(drafts.len(), 2);
+        assert_eq!(drafts[0].href, "https://example.com");
+        assert_eq!(drafts[0].title, "Edited");
+        assert_eq!(drafts[0].note, "check the link");
+
+        let remove = DraftCommands::Remove { id: 1 };
+        run(&context, &DraftArgs { command: remove }).await;
+        let drafts = read_drafts(&context);
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].id, 2);
+        // IDs aren't reused while newer drafts exist.
+        run(&context, &save("https://example.net", "Third")).await;
+        assert_eq!(read_drafts(&context)[1].id, 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn removes_drafts_once_submitted() {
+        let dir = test_dir("draft-submit");
+        let fixtures = dir.join("fixtures");
+        let fixture = json!({
+            "method": "POST",
+            "url": "https://news.kiwistand.com/messages",
+            "status": 200,
+            "response": "{}",
+        });
+        atomic::write(&fixtures.join("0001.json"), fixture.to_string()).unwrap();
+        LocalWallet::new_keystore(&dir, &mut rand::thread_rng(), "secret", Some("key")).unwrap();
+        let context = test_context(&[
+            "--state-dir",
+            dir.to_str().unwrap(),
+            "--replay",
+            fixtures.to_str().unwrap(),
+            "--yes",
+        ]);
+        run(&context, &save("https://example.com", "Example")).await;
+        let submit = DraftCommands::Submit(SubmitDraftArgs {
+            id: 1,
+            password: Some(String::from("secret")),
+            ledger: false,
+            address_index: None,
+        });
+        run(&context, &DraftArgs { command: submit }).await;
+        assert!(read_drafts(&context).is_empty());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn names_shared_drafts_by_link() {
+        assert_eq!(shared_name("https://example.com"), shared_name("https://example.com"));
+        assert_ne!(shared_name("https://example.com"), shared_name("https://example.org"));
+        assert_eq!(shared_name("https://example.com").len(), "draft-.toml".len() + 16);
+    }
+
+    fn draft(id: u64, title: &str, note: &str) -> Draft {
+        Draft {
+            id,
+            href: String::from("https://example.com"),
+            title: title.to_string(),
+            note: note.to_string(),
+        }
+    }
+
+    #[test]
+    fn shares_drafts_as_toml() {
+        let shared = draft(3, "Example", "check the link").to_toml();
+        let parsed = Draft::from_toml(&shared, 1).unwrap();
+        assert_eq!(parsed.id, 1);
+        assert_eq!(parsed.href, "https://example.com");
+        assert_eq!(parsed.title, "Example");
+        assert_eq!(parsed.note, "check the link");
+        assert!(Draft::from_toml("{\"href\": \"https://example.com\"}", 1).is_err());
+    }
+
+    #[test]
+    fn merges_drafts_and_reports_conflicts() {
+        let mut ours = draft(1, "Example", "");
+        assert!(ours.merge(&draft(0, "", "check the link")).is_empty());
+        assert_eq!(ours.title, "Example");
+        assert_eq!(ours.note, "check the link");
+        assert_eq!(ours.merge(&draft(0, "Other", "check the link")), vec!["title"]);
+        assert_eq!(ours.title, "Example");
+    }
+
+    #[test]
+    fn imports_and_exports_without_losing_edits() {
+        let dir = test_dir("draft-share");
+        let mut drafts = vec![draft(1, "Example", "")];
+        export_dir(&drafts, &dir, false);
+        let path = dir.join(shared_name("https://example.com"));
+        atomic::write(&path, draft(0, "Example", "check the link").to_toml()).unwrap();
+        import_dir(&mut drafts, &dir);
+        assert_eq!(drafts.len(), 1);
+        assert_eq!(drafts[0].note, "check the link");
+
+        // A teammate's conflicting title is neither imported nor overwritten.
+        atomic::write(&path, draft(0, "Theirs", "check the link").to_toml()).unwrap();
+        import_dir(&mut drafts, &dir);
+        assert_eq!(drafts[0].title, "Example");
+        export_dir(&drafts, &dir, false);
+        assert_eq!(read_shared(&path).title, "Theirs");
+        export_dir(&drafts, &dir, true);
+        assert_eq!(read_shared(&path).title, "Example");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
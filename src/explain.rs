@@ -0,0 +1,94 @@
+// @format
+use clap::ValueEnum;
+use ethers::{
+    core::types::{transaction::eip712::Eip712, Signature, U256},
+    utils::hex,
+};
+use kiwistand_core::{canonical, Message};
+
+/// The EIP-712 type string of `Message`. A unit test keeps it in sync with the derived type hash.
+const MESSAGE_TYPE: &str = "Message(string title,string href,string type,uint256 timestamp)";
+
+/// The topics `explain` knows about.
+#[derive(Clone, ValueEnum)]
+pub enum Topic {
+    /// The EIP-712 domain and type used for signing messages.
+    Eip712,
+    /// The JSON payload that is sent to the nodes.
+    Message,
+}
+
+/// Returns the message used for the rendered examples.
+fn example_message() -> Message {
+    Message {
+        title: String::from("hello world"),
+        href: String::from("https://example.com"),
+        r#type: String::from("amplify"),
+        timestamp: U256::from(1676559616),
+    }
+}
+
+/// Prints the explanation for the given topic.
+pub fn explain(topic: &Topic) {
+    println!(
+        "kiwistand-cli {} protocol reference\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    match topic {
+        Topic::Eip712 => explain_eip712(),
+        Topic::Message => explain_message(),
+    }
+}
+
+/// Prints the EIP-712 domain, the type and the digest of the example message.
+fn explain_eip712() {
+    let message = example_message();
+    let domain = message
+        .domain()
+        .expect("Couldn't build the EIP-712 domain");
+    let type_hash = Message::type_hash().expect("Couldn't compute the type hash");
+    let digest = message
+        .encode_eip712()
+        .expect("Couldn't compute the EIP-712 digest");
+
+    println!("Messages are signed as EIP-712 typed data with the primary type `Message`.\n");
+    println!("Domain:");
+    println!("{}\n", serde_json::to_string_pretty(&domain).unwrap());
+    println!("Type:      {}", MESSAGE_TYPE);
+    println!("Type hash: 0x{}\n", hex::encode(type_hash));
+    println!("Example message:");
+    println!("{:#?}\n", message);
+    println!("Digest:    0x{}", hex::encode(digest));
+}
+
+/// Prints the fields of the JSON payload and an example of it.
+fn explain_message() {
+    let message = example_message();
+    let placeholder = Signature {
+        r: U256::zero(),
+        s: U256::zero(),
+        v: 27,
+    };
+
+    println!("Signed messages are posted as JSON to the node's /messages endpoint.\n");
+    println!("  title      the story's title, empty when voting");
+    println!("  href       the link that is amplified");
+    println!("  type       always \"amplify\", votes resubmit a link with an empty title");
+    println!("  timestamp  Unix time in seconds at which the message was created");
+    println!("  signature  EIP-712 signature over the fields above, see `explain eip712`\n");
+    println!("The fields are serialized in exactly this order, without whitespace.\n");
+    println!("Example, with a placeholder signature:");
+    println!("{}", canonical::serialize(&message, &placeholder));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::keccak256;
+
+    #[test]
+    fn message_type_matches_derived_type_hash() {
+        let expected = Message::type_hash().unwrap();
+        assert_eq!(keccak256(MESSAGE_TYPE), expected);
+    }
+}
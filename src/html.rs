@@ -0,0 +1,227 @@
+// @format
+
+/// Elements whose content isn't part of a page's readable text.
+const NOISE: [&str; 9] = [
+    "script", "style", "noscript", "svg", "nav", "header", "footer", "aside", "form",
+];
+
+/// Elements that start a new line in the readable text.
+const BLOCKS: [&str; 16] = [
+    "p", "br", "div", "li", "h1", "h2", "h3", "h4", "h5", "h6", "tr", "blockquote", "pre",
+    "section", "article", "hr",
+];
+
+/// Returns the value of the quoted attribute `name` within an HTML tag.
+pub fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lowercase = tag.to_ascii_lowercase();
+    let start = lowercase.find(&format!("{}=", name))? + name.len() + 1;
+    let quote = tag[start..].chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value = &tag[start + 1..];
+    let end = value.find(quote)?;
+    Some(value[..end].to_string())
+}
+
+/// Removes all tags from an HTML fragment and collapses its whitespace.
+pub fn strip_tags(fragment: &str) -> String {
+    let mut text = String::new();
+    let mut in_tag = false;
+    for c in fragment.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Decodes the HTML entities that commonly appear in text and attributes.
+pub fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// Escapes text so it can be placed into HTML elements and quoted attributes.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Returns the content of the `<meta>` tag whose `property` or `name` is `key`, e.g. `og:image`.
+pub fn meta(html: &str, key: &str) -> Option<String> {
+    let lowercase = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find("<meta") {
+        let start = offset + start;
+        let end = match lowercase[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let tag = &html[start..end];
+        let name = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+        if name.is_some_and(|name| name.eq_ignore_ascii_case(key)) {
+            return attribute(tag, "content")
+                .map(|content| decode_entities(&content))
+                .filter(|content| !content.is_empty());
+        }
+        offset = end;
+    }
+    None
+}
+
+/// Returns the content of the first `name` element, without its tags.
+fn inner<'a>(html: &'a str, name: &str) -> Option<&'a str> {
+    let lowercase = html.to_ascii_lowercase();
+    let open = lowercase.find(&format!("<{}", name))?;
+    let start = open + lowercase[open..].find('>')? + 1;
+    let end = start + lowercase[start..].find(&format!("</{}>", name))?;
+    Some(&html[start..end])
+}
+
+/// Returns the page's `<title>`.
+pub fn title(html: &str) -> Option<String> {
+    let title = decode_entities(&strip_tags(inner(html, "title")?));
+    Some(title).filter(|title| !title.is_empty())
+}
+
+/// Removes the elements called `name`, including their content.
+fn remove_elements(html: &str, name: &str) -> String {
+    let lowercase = html.to_ascii_lowercase();
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut kept = String::new();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find(&open) {
+        let start = offset + start;
+        // Only match whole tag names, e.g. `<nav>` but not `<navigation>`.
+        let next = lowercase[start + open.len()..].chars().next();
+        if !matches!(next, Some('>' | ' ' | '\t' | '\n' | '\r' | '/')) {
+            kept.push_str(&html[offset..start + open.len()]);
+            offset = start + open.len();
+            continue;
+        }
+        kept.push_str(&html[offset..start]);
+        offset = match lowercase[start..].find(&close) {
+            Some(end) => start + end + close.len(),
+            None => html.len(),
+        };
+    }
+    kept.push_str(&html[offset..]);
+    kept
+}
+
+/// Returns the readable text of a page, one paragraph per line.
+///
+/// The main content is taken from `<article>`, `<main>` or `<body>`, in that order, after
+/// removing navigation, scripts and similar noise.
+pub fn readable_text(html: &str) -> String {
+    let mut html = html.to_string();
+    for name in NOISE {
+        html = remove_elements(&html, name);
+    }
+    let content = inner(&html, "article")
+        .or_else(|| inner(&html, "main"))
+        .or_else(|| inner(&html, "body"))
+        .unwrap_or(&html);
+
+    let mut text = String::new();
+    let mut tag = String::new();
+    let mut in_tag = false;
+    for c in content.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+                tag.clear();
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                let name = tag
+                    .trim_start_matches('/')
+                    .split(|c: char| c.is_whitespace() || c == '/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_ascii_lowercase();
+                if BLOCKS.contains(&name.as_str()) {
+                    text.push('\n');
+                }
+            }
+            c if in_tag => tag.push(c),
+            c => text.push(c),
+        }
+    }
+
+    decode_entities(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+/// Wraps every line of `text` at word boundaries so it fits into `width` columns.
+pub fn wrap(text: &str, width: usize) -> String {
+    text.lines()
+        .map(|line| {
+            let mut wrapped = String::new();
+            let mut column = 0;
+            for word in line.split(' ') {
+                let length = word.chars().count();
+                if column > 0 && column + 1 + length > width {
+                    wrapped.push('\n');
+                    column = 0;
+                } else if column > 0 {
+                    wrapped.push(' ');
+                    column += 1;
+                }
+                wrapped.push_str(word);
+                column += length;
+            }
+            wrapped
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extracts_readable_text() {
+        let html = r#"<html><head><title>A &amp; B</title><style>p { color: red }</style></head>
+            <body><nav><a href="/">Home</a></nav><navigation>kept</navigation>
+            <article><h1>Heading</h1><p>First <em>paragraph</em>.</p><script>alert(1)</script>
+            <p>Second<br>line</p></article></body></html>"#;
+        assert_eq!(title(html), Some(String::from("A & B")));
+        assert_eq!(
+            readable_text(html),
+            "Heading\n\nFirst paragraph.\n\nSecond\n\nline"
+        );
+    }
+
+    #[test]
+    fn reads_meta_tags() {
+        let html = r#"<meta name="description" content="Plain"><META property="og:title" content="A &amp; B">"#;
+        assert_eq!(meta(html, "og:title"), Some(String::from("A & B")));
+        assert_eq!(meta(html, "description"), Some(String::from("Plain")));
+        assert_eq!(meta(html, "og:image"), None);
+        assert_eq!(escape("<a href=\"x\">"), "&lt;a href=&quot;x&quot;&gt;");
+    }
+
+    #[test]
+    fn wraps_lines() {
+        assert_eq!(wrap("aaa bbb ccc", 7), "aaa bbb\nccc");
+    }
+}
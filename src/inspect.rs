@@ -0,0 +1,252 @@
+// @format
+use ethers::{
+    core::types::{transaction::eip712::Eip712, Address, Signature, U256},
+    utils::{hex, to_checksum},
+};
+use kiwistand_core::{canonical, limits, normalize, Message};
+use serde_json::{Map, Value};
+
+use std::fs;
+use std::path::Path;
+
+use crate::{read_stdin, Context};
+
+/// The fields a message payload consists of.
+const FIELDS: [&str; 5] = ["title", "href", "type", "timestamp", "signature"];
+
+/// How far, in seconds, a timestamp may lie in the future before it's flagged.
+const FUTURE_TOLERANCE: u64 = 5 * 60;
+
+/// `Report` contains the labeled fields and the problems found while inspecting a message.
+#[derive(Default)]
+struct Report {
+    fields: Vec<(&'static str, String)>,
+    signer: Option<Address>,
+    canonical: Option<String>,
+    problems: Vec<String>,
+}
+
+/// Reads the message at `path`, or from stdin if it's `-`, and prints a report about it.
+///
+/// Returns false if any problem was found with the message.
+pub fn inspect(context: &Context, path: &Path) -> bool {
+    let contents = if path == Path::new("-") {
+        read_stdin()
+    } else {
+        fs::read_to_string(path).expect("Couldn't read the message file")
+    };
+    let value: Value = match serde_json::from_str(&contents) {
+        Ok(value) => value,
+        Err(error) => {
+            println!("problem:   message isn't valid JSON: {}", error);
+            return false;
+        }
+    };
+    println!("{}\n", serde_json::to_string_pretty(&value).unwrap());
+
+    let report = inspect_value(&value, context.clock.now());
+    for (label, field) in &report.fields {
+        println!("{:<10} {}", label, field);
+    }
+    if let Some(canonical) = &report.canonical {
+        if contents.trim() == canonical {
+            println!("{:<10} yes", "canonical");
+        } else {
+            println!("{:<10} no, the canonical form is {}", "canonical", canonical);
+        }
+    }
+    for problem in &report.problems {
+        println!("problem:   {}", problem);
+    }
+    println!("\nNormalization: {}.", normalize::RULES);
+    report.problems.is_empty()
+}
+
+/// Checks every field of a message payload and recovers its signer.
+fn inspect_value(value: &Value, now: u64) -> Report {
+    let mut report = Report::default();
+    let object = match value.as_object() {
+        Some(object) => object,
+        None => {
+            report.problems.push(String::from("message isn't a JSON object"));
+            return report;
+        }
+    };
+    for key in object.keys() {
+        if !FIELDS.contains(&key.as_str()) {
+            report.problems.push(format!("unknown field `{}`", key));
+        }
+    }
+
+    let title = string_field(object, "title", &mut report);
+    if let Some(title) = &title {
+        let kind = if title.is_empty() { "vote" } else { "submission" };
+        report.fields.push(("title", format!("{:?} ({})", title, kind)));
+    }
+    let href = string_field(object, "href", &mut report);
+    if let Some(href) = &href {
+        report.fields.push(("href", href.clone()));
+        if !href.starts_with("https://") && !href.starts_with("http://") {
+            report.problems.push(String::from("href isn't an http(s) URL"));
+        }
+    }
+    let r#type = string_field(object, "type", &mut report);
+    if let Some(r#type) = &r#type {
+        report.fields.push(("type", r#type.clone()));
+        if r#type != "amplify" {
+            report
+                .problems
+                .push(format!("type must be \"amplify\", found {:?}", r#type));
+        }
+    }
+    let timestamp = timestamp_field(object, now, &mut report);
+    let signature = signature_field(object, &mut report);
+
+    if let (Some(title), Some(href)) = (&title, &href) {
+        report.problems.extend(limits::violations(href, title));
+        if normalize::title(title) == *title && normalize::href(href) == *href {
+            report.fields.push(("normalized", String::from("yes")));
+        } else {
+            report.fields.push((
+                "normalized",
+                String::from("no, this CLI would sign the trimmed title and href"),
+            ));
+        }
+    }
+
+    if let (Some(title), Some(href), Some(r#type), Some(timestamp)) = (title, href, r#type, timestamp)
+    {
+        let message = Message {
+            title,
+            href,
+            r#type,
+            timestamp: U256::from(timestamp),
+        };
+        let digest = message
+            .encode_eip712()
+            .expect("Couldn't compute the EIP-712 digest");
+        report.fields.push(("digest", format!("0x{}", hex::encode(digest))));
+        report.fields.push(("id", message.id()));
+        if let Some(signature) = signature {
+            report.canonical = Some(canonical::serialize(&message, &signature));
+            match signature.recover(digest) {
+                Ok(signer) => {
+                    report.fields.push(("signer", to_checksum(&signer, None)));
+                    report.signer = Some(signer);
+                }
+                Err(error) => report
+                    .problems
+                    .push(format!("couldn't recover the signer: {}", error)),
+            }
+        }
+    }
+    report
+}
+
+/// Returns the string field `name`, recording a problem if it's missing or not a string.
+fn string_field(object: &Map<String, Value>, name: &str, report: &mut Report) -> Option<String> {
+    match object.get(name) {
+        Some(Value::String(value)) => Some(value.clone()),
+        Some(_) => {
+            report.problems.push(format!("{} must be a string", name));
+            None
+        }
+        None => {
+            report.problems.push(format!("{} is missing", name));
+            None
+        }
+    }
+}
+
+/// Returns the timestamp, recording a problem if it's malformed or too far in the future.
+fn timestamp_field(object: &Map<String, Value>, now: u64, report: &mut Report) -> Option<u64> {
+    let timestamp = match object.get("timestamp") {
+        Some(Value::Number(number)) => number.as_u64(),
+        Some(_) => None,
+        None => {
+            report.problems.push(String::from("timestamp is missing"));
+            return None;
+        }
+    };
+    let timestamp = match timestamp {
+        Some(timestamp) => timestamp,
+        None => {
+            report
+                .problems
+                .push(String::from("timestamp must be a non-negative integer"));
+            return None;
+        }
+    };
+    if timestamp > now {
+        report
+            .fields
+            .push(("timestamp", format!("{} (in {}s)", timestamp, timestamp - now)));
+        if timestamp - now > FUTURE_TOLERANCE {
+            report
+                .problems
+                .push(String::from("timestamp lies too far in the future"));
+        }
+    } else {
+        report
+            .fields
+            .push(("timestamp", format!("{} ({}s ago)", timestamp, now - timestamp)));
+    }
+    Some(timestamp)
+}
+
+/// Returns the parsed signature, recording a problem if it's missing or malformed.
+fn signature_field(object: &Map<String, Value>, report: &mut Report) -> Option<Signature> {
+    let signature = string_field(object, "signature", report)?;
+    report.fields.push(("signature", signature.clone()));
+    if !signature.starts_with("0x") {
+        report
+            .problems
+            .push(String::from("signature must be 0x-prefixed"));
+    }
+    match signature.parse::<Signature>() {
+        Ok(signature) => Some(signature),
+        Err(error) => {
+            report
+                .problems
+                .push(format!("signature is malformed: {}", error));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn recovers_signer_of_valid_message() {
+        let message = json!({
+            "title": "hello world",
+            "href": "https://example.com",
+            "type": "amplify",
+            "timestamp": 1676559616,
+            "signature": "0x1df128dfe1f86df4e20ecc6ebbd586e0ab56e3fc8d0db9210422c3c765633ad8793af68aa232cf39cc3f75ea18f03260258f7276c2e0d555f98e1cf16672dd201c",
+        });
+        let report = inspect_value(&message, 1676559616);
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        let expected: Address = "0x0f6A79A579658E401E0B81c6dde1F2cd51d97176"
+            .parse()
+            .unwrap();
+        assert_eq!(report.signer, Some(expected));
+    }
+
+    #[test]
+    fn flags_malformed_fields() {
+        let message = json!({
+            "title": 1,
+            "href": "example.com",
+            "type": "upvote",
+            "timestamp": 1676559616 + FUTURE_TOLERANCE + 1,
+            "extra": true,
+        });
+        let report = inspect_value(&message, 1676559616);
+        assert_eq!(report.problems.len(), 6, "{:?}", report.problems);
+        assert_eq!(report.signer, None);
+    }
+}
@@ -0,0 +1,247 @@
+// @format
+use clap::{Args, Subcommand};
+use ethers::{
+    signers::{coins_bip39::English, LocalWallet, MnemonicBuilder, Signer},
+    utils::to_checksum,
+};
+use kiwistand_api::atomic;
+use serde_json::{json, Value};
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{lock, prompt_hidden, secret, Context};
+
+/// `KeyArgs` contains the key subcommand to run.
+#[derive(Args)]
+pub struct KeyArgs {
+    #[command(subcommand)]
+    command: KeyCommands,
+}
+
+/// The subcommands for managing the keystore and the named accounts.
+#[derive(Subcommand)]
+enum KeyCommands {
+    /// Creates a new key and encrypts it into the keystore, printing its address.
+    Generate {
+        /// The password to encrypt the keystore with, asked for if not given.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Derives a key from a BIP-39 seed phrase, which is asked for, and encrypts it into the
+    /// keystore, printing its address.
+    ImportMnemonic {
+        /// The account index to derive, as numbered by wallets like MetaMask starting at 0.
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// The full derivation path, instead of the default path with the account index.
+        #[arg(long, conflicts_with = "index")]
+        derivation_path: Option<String>,
+        /// The password to encrypt the keystore with, asked for if not given.
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// Lists the named accounts, marking the one that is signed with.
+    List,
+    /// Makes the named account the one to sign with unless --account is given, or goes back to
+    /// the keystore `key` if no name is given.
+    Use { name: Option<String> },
+}
+
+/// Panics if `name` can't be used as the name of an account's keystore file.
+pub fn check_name(name: &str) {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        fail!("{:?} isn't a valid account name, use e.g. letters, digits and dashes", name);
+    }
+}
+
+/// Returns the path of the file storing the default account.
+fn accounts_path(context: &Context) -> PathBuf {
+    context
+        .state_file("accounts.json")
+        .unwrap_or_else(|| fail!("accounts need a state directory, see --state-dir"))
+}
+
+/// Returns the account to sign with when --account isn't given, if one was chosen.
+pub fn default_account(context: &Context) -> Option<String> {
+    let path = context.state_file("accounts.json")?;
+    if !path.exists() {
+        return None;
+    }
+    let contents = fs::read_to_string(path).expect("Couldn't read the accounts");
+    let accounts: Value = serde_json::from_str(&contents).expect("Couldn't parse the accounts");
+    accounts["default"].as_str().map(String::from)
+}
+
+/// Returns the names of all accounts, sorted.
+fn accounts(context: &Context) -> Vec<String> {
+    let dir = match context.state_file("keys") {
+        Some(dir) if dir.exists() => dir,
+        _ => return Vec::new(),
+    };
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .expect("Couldn't read the accounts directory")
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter(|name| !name.starts_with('.'))
+        .collect();
+    names.sort();
+    names
+}
+
+/// Returns the password to encrypt a new keystore with: the given one, the one from a secret
+/// provider, or one typed in twice.
+fn new_password(context: &Context, password: &Option<String>) -> String {
+    if let Some(password) = password.clone().or_else(|| secret::password(context)) {
+        return password;
+    }
+    let password = prompt_hidden("New keystore password:");
+    if prompt_hidden("Repeat the password:") != password {
+        fail!("The passwords don't match");
+    }
+    password
+}
+
+/// Panics if there already is a keystore at `path`, as it would be lost.
+fn check_free(path: &Path) {
+    if path.exists() {
+        fail!(
+            "There already is a keystore at {}. Move it away first, or pass --keystore with \
+             another path",
+            path.display()
+        );
+    }
+}
+
+/// Splits the keystore path into its directory, which is created if missing, and file name.
+fn keystore_location(path: &Path) -> (&Path, &str) {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let name = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .expect("The keystore path has no file name");
+    fs::create_dir_all(dir).expect("Couldn't create the keystore's directory");
+    (dir, name)
+}
+
+/// Encrypts a new random key into a keystore at `path` and returns the key.
+fn generate(path: &Path, password: &str) -> LocalWallet {
+    let (dir, name) = keystore_location(path);
+    let (wallet, _) = LocalWallet::new_keystore(dir, &mut rand::thread_rng(), password, Some(name))
+        .expect("Couldn't write the keystore");
+    wallet
+}
+
+/// Derives the key at `derivation_path`, or at the default path with `index`, from `phrase`.
+fn derive(phrase: &str, index: u32, derivation_path: &Option<String>) -> LocalWallet {
+    let words = phrase.split_whitespace().collect::<Vec<_>>().join(" ");
+    let builder = MnemonicBuilder::<English>::default().phrase(words.as_str());
+    let builder = match derivation_path {
+        Some(path) => builder.derivation_path(path),
+        None => builder.index(index),
+    }
+    .unwrap_or_else(|error| fail!("Invalid derivation path: {}", error));
+    builder
+        .build()
+        .unwrap_or_else(|error| fail!("Couldn't derive a key from the seed phrase: {}", error))
+}
+
+/// Encrypts the key of `wallet` into a keystore at `path`.
+fn import(path: &Path, wallet: &LocalWallet, password: &str) {
+    let (dir, name) = keystore_location(path);
+    let key = wallet.signer().to_bytes();
+    eth_keystore::encrypt_key(dir, &mut rand::thread_rng(), key, password, Some(name))
+        .expect("Couldn't write the keystore");
+}
+
+/// Runs a key subcommand.
+pub fn run(context: &Context, args: &KeyArgs) {
+    match &args.command {
+        KeyCommands::Generate { password } => {
+            let path = context.keystore();
+            check_free(&path);
+            let password = new_password(context, password);
+            let wallet = generate(&path, &password);
+            eprintln!("Wrote the new keystore to {}", path.display());
+            println!("{}", to_checksum(&wallet.address(), None));
+        }
+        KeyCommands::ImportMnemonic {
+            index,
+            derivation_path,
+            password,
+        } => {
+            let path = context.keystore();
+            check_free(&path);
+            let phrase = prompt_hidden("Seed phrase:");
+            let wallet = derive(&phrase, *index, derivation_path);
+            let password = new_password(context, password);
+            import(&path, &wallet, &password);
+            eprintln!("Wrote the imported key to {}", path.display());
+            println!("{}", to_checksum(&wallet.address(), None));
+        }
+        KeyCommands::List => {
+            let active = context.account();
+            for name in accounts(context) {
+                let marker = if active.as_ref() == Some(&name) { "*" } else { " " };
+                println!("{} {}", marker, name);
+            }
+        }
+        KeyCommands::Use { name } => {
+            let _lock = lock::lock_state(context);
+            if let Some(name) = name {
+                check_name(name);
+                if !accounts(context).contains(name) {
+                    fail!("There is no account {}, see `key list`", name);
+                }
+            }
+            let contents = serde_json::to_string_pretty(&json!({ "default": name })).unwrap();
+            atomic::write(&accounts_path(context), contents).expect("Couldn't write the accounts");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{read_key, test_dir};
+
+    const PHRASE: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn generated_keystores_decrypt_with_their_password() {
+        let dir = test_dir("key-generate");
+        let path = dir.join("keys").join("bot");
+        let wallet = generate(&path, "secret");
+        let decrypted = read_key(&path, &String::from("secret"));
+        assert_eq!(decrypted.address(), wallet.address());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn accepts_plain_account_names() {
+        check_name("bot");
+        check_name("personal-2");
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't a valid account name")]
+    fn rejects_account_names_leaving_the_keys_directory() {
+        check_name("../key");
+    }
+
+    #[test]
+    fn derives_accounts_like_other_wallets() {
+        let first = derive(PHRASE, 0, &None);
+        assert_eq!(
+            to_checksum(&first.address(), None),
+            "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+        );
+        let second = derive(&format!("  {}\n", PHRASE), 1, &None);
+        assert_eq!(
+            to_checksum(&second.address(), None),
+            "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+        );
+        let path = Some(String::from("m/44'/60'/0'/0/1"));
+        assert_eq!(derive(PHRASE, 0, &path).address(), second.address());
+    }
+}
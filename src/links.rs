@@ -0,0 +1,186 @@
+// @format
+use std::fs;
+use std::path::Path;
+
+use crate::html::{attribute, decode_entities, strip_tags};
+use crate::{create_message, prompt, send, Context};
+
+/// A link found in a document, with its anchor text as the suggested title.
+#[derive(Debug, PartialEq)]
+struct Link {
+    href: String,
+    title: String,
+}
+
+/// Lists the links in the HTML or Markdown file at `path` and submits the ones the user picks.
+///
+/// For every picked link the anchor text is offered as the title, which the user can replace.
+pub async fn submit_extracted(context: &Context, password: &String, path: &Path) {
+    let contents = fs::read_to_string(path).expect("Couldn't read the file to extract links from");
+    let links = extract(&contents);
+    if links.is_empty() {
+        println!("No links found in {}", path.display());
+        return;
+    }
+    for (i, link) in links.iter().enumerate() {
+        println!("{:>3}. {}\n     {}", i + 1, link.title, link.href);
+    }
+
+    let selection = prompt("Links to submit (e.g. 1,3-5 or all):");
+    let picked = match parse_selection(&selection, links.len()) {
+        Ok(picked) => picked,
+        Err(error) => fail!("Invalid selection: {}", error),
+    };
+    for index in picked {
+        let link = &links[index];
+        let answer = prompt(&format!("Title for {} [{}]:", link.href, link.title));
+        let title = if answer.trim().is_empty() {
+            link.title.clone()
+        } else {
+            answer.trim().to_string()
+        };
+        if let Some(message) =
+            create_message(context, password, &link.href, &title, false, None).await
+        {
+            send(context, message).await;
+        }
+    }
+}
+
+/// Returns the http(s) links of an HTML or Markdown document in order, without duplicates.
+fn extract(contents: &str) -> Vec<Link> {
+    let mut links: Vec<Link> = Vec::new();
+    for link in extract_html(contents)
+        .into_iter()
+        .chain(extract_markdown(contents))
+    {
+        let is_web = link.href.starts_with("https://") || link.href.starts_with("http://");
+        if is_web && !links.iter().any(|known| known.href == link.href) {
+            links.push(link);
+        }
+    }
+    links
+}
+
+/// Returns the links of `<a href="...">text</a>` elements.
+fn extract_html(contents: &str) -> Vec<Link> {
+    let lowercase = contents.to_ascii_lowercase();
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = lowercase[offset..].find("<a ") {
+        let tag_start = offset + start;
+        let tag_end = match lowercase[tag_start..].find('>') {
+            Some(end) => tag_start + end,
+            None => break,
+        };
+        let text_end = match lowercase[tag_end..].find("</a>") {
+            Some(end) => tag_end + end,
+            None => break,
+        };
+        if let Some(href) = attribute(&contents[tag_start..tag_end], "href") {
+            links.push(Link {
+                href: decode_entities(&href),
+                title: decode_entities(&strip_tags(&contents[tag_end + 1..text_end])),
+            });
+        }
+        offset = text_end;
+    }
+    links
+}
+
+/// Returns the links of `[text](url)` Markdown inline links.
+fn extract_markdown(contents: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut offset = 0;
+    while let Some(start) = contents[offset..].find('[') {
+        let text_start = offset + start + 1;
+        let text_end = match contents[text_start..].find("](") {
+            Some(end) => text_start + end,
+            None => break,
+        };
+        let href_start = text_end + 2;
+        let href_end = match contents[href_start..].find(')') {
+            Some(end) => href_start + end,
+            None => break,
+        };
+        let text = &contents[text_start..text_end];
+        // A `[` within the text means the first bracket didn't open this link.
+        let text = text.rsplit('[').next().unwrap_or(text);
+        let href = contents[href_start..href_end].split_whitespace().next();
+        if let Some(href) = href {
+            links.push(Link {
+                href: href.to_string(),
+                title: text.split_whitespace().collect::<Vec<&str>>().join(" "),
+            });
+        }
+        offset = href_end;
+    }
+    links
+}
+
+/// Parses a selection like `1,3-5` or `all` into zero-based indices of `count` items.
+fn parse_selection(selection: &str, count: usize) -> Result<Vec<usize>, String> {
+    if selection.trim() == "all" {
+        return Ok((0..count).collect());
+    }
+    let mut indices = Vec::new();
+    for part in selection.split(',').map(str::trim).filter(|part| !part.is_empty()) {
+        let (first, last) = match part.split_once('-') {
+            Some((first, last)) => (first.trim(), last.trim()),
+            None => (part, part),
+        };
+        let first: usize = first.parse().map_err(|_| format!("`{}` isn't a number", first))?;
+        let last: usize = last.parse().map_err(|_| format!("`{}` isn't a number", last))?;
+        if first == 0 || last > count || first > last {
+            return Err(format!("`{}` isn't within 1-{}", part, count));
+        }
+        for number in first..=last {
+            if !indices.contains(&(number - 1)) {
+                indices.push(number - 1);
+            }
+        }
+    }
+    Ok(indices)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(href: &str, title: &str) -> Link {
+        Link {
+            href: String::from(href),
+            title: String::from(title),
+        }
+    }
+
+    #[test]
+    fn extracts_html_links() {
+        let html = r#"<p><A class="x" HREF="https://example.com/a?x=1&amp;y=2">An <b>important</b>
+            post</A> and <a href='/relative'>skip</a> <a href="https://example.com/a?x=1&amp;y=2">dup</a></p>"#;
+        assert_eq!(
+            extract(html),
+            vec![link("https://example.com/a?x=1&y=2", "An important post")]
+        );
+    }
+
+    #[test]
+    fn extracts_markdown_links() {
+        let markdown = "- [x] read [Kiwi News](https://news.kiwistand.com \"title\") and [more](http://example.com).";
+        assert_eq!(
+            extract(markdown),
+            vec![
+                link("https://news.kiwistand.com", "Kiwi News"),
+                link("http://example.com", "more"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parses_selections() {
+        assert_eq!(parse_selection("1, 3-4,3", 5), Ok(vec![0, 2, 3]));
+        assert_eq!(parse_selection("all", 2), Ok(vec![0, 1]));
+        assert!(parse_selection("6", 5).is_err());
+        assert!(parse_selection("a", 5).is_err());
+    }
+}
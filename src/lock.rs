@@ -0,0 +1,54 @@
+// @format
+use std::fs::{self, File, OpenOptions, TryLockError};
+
+use crate::Context;
+
+/// `StateLock` holds the advisory lock on the state directory until it's dropped.
+pub struct StateLock {
+    _file: File,
+}
+
+/// Opens the lock file in the state directory, creating it if needed.
+fn open_lock(context: &Context) -> File {
+    let path = context
+        .state_file("lock")
+        .unwrap_or_else(|| fail!("locking needs a state directory, see --state-dir"));
+    fs::create_dir_all(path.parent().unwrap()).expect("Couldn't create the state directory");
+    OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .expect("Couldn't open the lock file")
+}
+
+/// Takes the lock on the state directory, so that concurrent invocations (e.g. cron and manual
+/// use) don't interleave their read-modify-write cycles on state files.
+///
+/// If another instance holds the lock this fails, or waits for it when `--wait` was given.
+pub fn lock_state(context: &Context) -> StateLock {
+    let file = open_lock(context);
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(TryLockError::WouldBlock) if context.wait => {
+            eprintln!("Waiting for another instance of kiwistand-cli to finish...");
+            file.lock().expect("Couldn't lock the state directory");
+        }
+        Err(TryLockError::WouldBlock) => {
+            fail!("Another instance of kiwistand-cli is running, pass --wait to wait for it")
+        }
+        Err(TryLockError::Error(error)) => panic!("Couldn't lock the state directory: {}", error),
+    }
+    StateLock { _file: file }
+}
+
+/// Takes the lock on the state directory like `lock_state`, but returns `None` instead of
+/// failing or waiting if another instance holds it.
+pub fn try_lock_state(context: &Context) -> Option<StateLock> {
+    let file = open_lock(context);
+    match file.try_lock() {
+        Ok(()) => Some(StateLock { _file: file }),
+        Err(TryLockError::WouldBlock) => None,
+        Err(TryLockError::Error(error)) => panic!("Couldn't lock the state directory: {}", error),
+    }
+}
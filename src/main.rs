@@ -1,25 +1,223 @@
 // @format
 use clap::{Args, Parser, Subcommand};
 use dirs::home_dir;
-#[allow(unused_imports)]
+#[cfg(feature = "ledger")]
+use ethers::signers::{HDPath, Ledger, LedgerError};
+use eth_keystore::KeystoreError;
 use ethers::{
-    contract::{Eip712, EthAbiType},
-    core::k256::ecdsa::SigningKey,
-    core::types::{transaction::eip712::Eip712, Signature, U256},
-    signers::{HDPath, Ledger, LocalWallet, Signer},
+    core::types::{Address, Signature, U256},
+    signers::{LocalWallet, Signer, WalletError},
+    utils::to_checksum,
 };
-use serde_json::{json, Value};
+use kiwistand_api::Transport;
+use kiwistand_core::{canonical, normalize, sign, Message};
 
-use std::path::{PathBuf};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Panics like `panic!`, for failures that are the user's to fix rather than bugs, such as a
+/// wrong password or a broken rule of the signing policy, so that no crash report is written.
+macro_rules! fail {
+    ($($arg:tt)*) => {{
+        crate::report::expect_failure();
+        panic!($($arg)*)
+    }};
+}
+
+mod audit;
+mod backup;
+mod bridge;
+mod clock;
+mod compat;
+mod draft;
+mod explain;
+mod html;
+mod inspect;
+mod key;
+mod links;
+mod lock;
+mod perf;
+mod policy;
+mod preflight;
+mod preview;
+mod report;
+mod secret;
+mod selftest;
+mod shutdown;
+mod speak;
+mod template;
+mod transform;
+mod xtest_js;
+
+use clock::{Clock, FixedClock, SystemClock};
 
 // Define the CLI parser and its options
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
 struct Cli {
+    /// Answer "yes" to every confirmation prompt.
+    #[arg(short, long, global = true, visible_alias = "no-input")]
+    yes: bool,
+    /// Wait for other running instances to finish instead of failing.
+    #[arg(long, global = true)]
+    wait: bool,
+    /// Never read or write `~/.kiwistand`, taking everything from flags and the environment.
+    #[arg(long, global = true)]
+    ephemeral: bool,
+    /// Directory to keep the CLI's state in, instead of `~/.kiwistand`.
+    #[arg(long, global = true, env = "KIWISTAND_STATE_DIR")]
+    state_dir: Option<PathBuf>,
+    /// Path of the keystore to sign with, instead of `key` in the state directory.
+    #[arg(long, global = true, env = "KIWISTAND_KEYSTORE")]
+    keystore: Option<PathBuf>,
+    /// Named account to sign with, i.e. the keystore `keys/<name>` in the state directory.
+    #[arg(long, global = true, env = "KIWISTAND_ACCOUNT", conflicts_with = "keystore")]
+    account: Option<String>,
+    /// Command printing the keystore password, e.g. `pass show kiwistand`.
+    #[arg(
+        long,
+        global = true,
+        env = "KIWISTAND_PASSWORD_COMMAND",
+        conflicts_with = "password_file"
+    )]
+    password_command: Option<String>,
+    /// File containing the keystore password, which must only be accessible by its owner.
+    #[arg(long, global = true, env = "KIWISTAND_PASSWORD_FILE")]
+    password_file: Option<PathBuf>,
+    /// Record every HTTP interaction as a fixture into the given directory.
+    #[arg(long, global = true, conflicts_with = "replay")]
+    record: Option<PathBuf>,
+    /// Replay HTTP interactions from fixtures in the given directory instead of the network.
+    #[arg(long, global = true)]
+    replay: Option<PathBuf>,
+    /// User-Agent to send with every HTTP request, instead of `kiwistand-cli/<version>`.
+    #[arg(long, global = true, env = "KIWISTAND_USER_AGENT")]
+    user_agent: Option<String>,
+    /// Contact address (e.g. an email) sent in the `From` header, for node operators.
+    #[arg(long, global = true, env = "KIWISTAND_CONTACT")]
+    contact: Option<String>,
+    /// Fail instead of correcting or guessing anything, e.g. overlong titles or piped links.
+    #[arg(long, global = true)]
+    strict: bool,
+    /// Sign even if the signing policy in `policy.toml` forbids it.
+    #[arg(long, global = true)]
+    override_policy: bool,
+    /// Command to pipe confirmations and results into, e.g. a text-to-speech program.
+    #[arg(long, global = true, env = "KIWISTAND_SPEAK")]
+    speak: Option<String>,
+    /// Back up the state files once a day, when the CLI is run.
+    #[arg(long, global = true, env = "KIWISTAND_BACKUP_DAILY")]
+    backup_daily: bool,
+    /// Never fetch third-party pages, e.g. for previews.
+    #[arg(long, global = true)]
+    no_fetch: bool,
+    /// Report how long each phase of the command took, e.g. keystore decryption and HTTP.
+    #[arg(long, global = true)]
+    profile_perf: bool,
+    /// Use the given Unix time instead of the system clock (for development).
+    #[arg(long, global = true, hide = true)]
+    fake_time: Option<u64>,
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
+}
+
+/// `Context` holds the options shared by all subcommands, resolved once from the global flags.
+struct Context {
+    assume_yes: bool,
+    strict: bool,
+    override_policy: bool,
+    speak: Option<String>,
+    backup_daily: bool,
+    wait: bool,
+    ephemeral: bool,
+    state_dir: Option<PathBuf>,
+    keystore_override: Option<PathBuf>,
+    account: Option<String>,
+    password_command: Option<String>,
+    password_file: Option<PathBuf>,
+    transport: Transport,
+    clock: Box<dyn Clock>,
+    perf: Arc<perf::Profiler>,
+}
+
+impl Context {
+    fn from_cli(cli: &Cli) -> Context {
+        let perf = Arc::new(perf::Profiler::new(cli.profile_perf));
+        Context {
+            assume_yes: cli.yes,
+            strict: cli.strict,
+            override_policy: cli.override_policy,
+            speak: cli.speak.clone(),
+            backup_daily: cli.backup_daily,
+            wait: cli.wait,
+            ephemeral: cli.ephemeral,
+            state_dir: cli.state_dir.clone(),
+            keystore_override: cli.keystore.clone(),
+            account: cli.account.clone(),
+            password_command: cli.password_command.clone(),
+            password_file: cli.password_file.clone(),
+            transport: Transport::new(
+                cli.record.clone(),
+                cli.replay.clone(),
+                cli.user_agent.clone(),
+                cli.contact.clone(),
+                cli.no_fetch,
+                perf.clone(),
+            ),
+            clock: match cli.fake_time {
+                Some(time) => Box::new(FixedClock(time)),
+                None => Box::new(SystemClock),
+            },
+            perf,
+        }
+    }
+
+    /// Returns the directory holding the CLI's state.
+    ///
+    /// This is `~/.kiwistand` unless overridden, and `None` in ephemeral mode without an override.
+    fn state_dir(&self) -> Option<PathBuf> {
+        match &self.state_dir {
+            Some(state_dir) => Some(state_dir.clone()),
+            None if self.ephemeral => None,
+            None => Some(get_config_path()),
+        }
+    }
+
+    /// Returns the path of the file `name` in the state directory, if there is one.
+    fn state_file(&self, name: &str) -> Option<PathBuf> {
+        self.state_dir().map(|state_dir| state_dir.join(name))
+    }
+
+    /// Returns the named account to sign with: the given one, or else the default one, if any.
+    fn account(&self) -> Option<String> {
+        let account = self.account.clone().or_else(|| key::default_account(self))?;
+        key::check_name(&account);
+        Some(account)
+    }
+
+    /// Returns the path of the keystore, if one is configured.
+    ///
+    /// This is the given keystore, the named account's keystore in `keys`, or else `key`.
+    fn keystore_path(&self) -> Option<PathBuf> {
+        if let Some(keystore) = &self.keystore_override {
+            return Some(keystore.clone());
+        }
+        match self.account() {
+            Some(account) => self.state_file("keys").map(|keys| keys.join(account)),
+            None => self.state_file("key"),
+        }
+    }
+
+    /// Returns the path of the keystore to sign with, panicking if there is none.
+    fn keystore(&self) -> PathBuf {
+        self.keystore_path().unwrap_or_else(|| {
+            fail!("--ephemeral requires the keystore to be given with --keystore or KIWISTAND_KEYSTORE")
+        })
+    }
 }
 
 /// An enumeration representing the various subcommands supported by the CLI.
@@ -28,207 +226,598 @@ enum Commands {
     /// Submits an article with a provided password, href, and title.
     Submit(SubmitArgs),
     /// Submits an article using a Ledger hardware wallet with a provided href, title, and optional address index.
+    #[cfg(feature = "ledger")]
     SubmitLedger(LedgerArgs),
     /// Votes for an article with a provided password and href.
     Vote(VoteArgs),
     /// Votes for an article using a Ledger hardware wallet with a provided href and optional address index.
+    #[cfg(feature = "ledger")]
     VoteLedger(VoteLedgerArgs),
+    /// Explains the protocol structures the CLI produces, with a rendered example.
+    Explain(ExplainArgs),
+    /// Inspects a message JSON file, recomputing its digest and recovering its signer.
+    Inspect(InspectArgs),
+    /// Compares digests and signatures against the kiwistand JS client (for development).
+    #[command(hide = true)]
+    XtestJs(XtestJsArgs),
+    /// Prints where each file the CLI uses is located.
+    Paths,
+    /// Manages the templates titles can be built from.
+    Template(template::TemplateArgs),
+    /// Announces events from other services as submissions.
+    Bridge(bridge::BridgeArgs),
+    /// Shows the readable text of a linked page.
+    Preview(PreviewArgs),
+    /// Shows the card a link would be displayed with, from the page's OpenGraph metadata.
+    PreviewCard(PreviewCardArgs),
+    /// Checks the log of every signature the CLI produced.
+    Audit(audit::AuditArgs),
+    /// Shows the logged signature with the given ID.
+    Show(ShowArgs),
+    /// Backs up and restores the state files.
+    Backup(backup::BackupArgs),
+    /// Manages submissions saved to be submitted later.
+    Draft(draft::DraftArgs),
+    /// Compares the CLI's payloads with those of other clients.
+    Compat(compat::CompatArgs),
+    /// Creates and manages the keystore.
+    Key(key::KeyArgs),
+    /// Prints the checksummed address that signs, from the keystore or a Ledger.
+    Whoami(WhoamiArgs),
+    /// Signs, verifies and submits a canary message with a throwaway key to check the setup.
+    Selftest(selftest::SelftestArgs),
 }
 
 /// `SubmitArgs` contains the password, href, and title for submitting a new article.
 #[derive(Args)]
 struct SubmitArgs {
+    /// The keystore password, unless --password-command or --password-file provides it.
+    #[arg(long)]
     password: Option<String>,
     href: Option<String>,
     title: Option<String>,
+    #[command(flatten)]
+    template: template::TemplateOptions,
+    #[command(flatten)]
+    transform: transform::TransformOptions,
+    /// Pick the links to submit from an HTML or Markdown file instead.
+    #[arg(long, requires = "interactive")]
+    extract_links: Option<PathBuf>,
+    /// Ask which of the extracted links to submit and with which titles.
+    #[arg(long, requires = "extract_links")]
+    interactive: bool,
 }
 
 /// `LedgerArgs` contains the href, title, and address index for submitting a new article using a Ledger device.
+#[cfg(feature = "ledger")]
 #[derive(Args)]
 struct LedgerArgs {
     href: Option<String>,
     title: Option<String>,
     address_index: Option<usize>,
+    #[command(flatten)]
+    template: template::TemplateOptions,
+    #[command(flatten)]
+    transform: transform::TransformOptions,
 }
 
 /// `VoteArgs` contains the password and href for upvoting an article.
 #[derive(Args)]
 struct VoteArgs {
+    /// The keystore password, unless --password-command or --password-file provides it.
+    #[arg(long)]
     password: Option<String>,
     href: Option<String>,
 }
 
 /// `VoteLedgerArgs` contains the href and address index for upvoting an article using a Ledger device.
+#[cfg(feature = "ledger")]
 #[derive(Args)]
 struct VoteLedgerArgs {
     href: Option<String>,
     address_index: Option<usize>,
 }
 
+/// `WhoamiArgs` contains the password of the keystore, or the Ledger to show the address of.
+#[derive(Args)]
+struct WhoamiArgs {
+    /// The keystore password, asked for unless given or provided by --password-command or
+    /// --password-file.
+    #[arg(long)]
+    password: Option<String>,
+    /// Show the address of the Ledger instead of the keystore.
+    #[arg(long, conflicts_with = "password")]
+    ledger: bool,
+    /// The Ledger address index to show the address of.
+    #[arg(long, requires = "ledger")]
+    address_index: Option<usize>,
+}
+
+/// `ExplainArgs` contains the topic to explain.
+#[derive(Args)]
+struct ExplainArgs {
+    topic: explain::Topic,
+}
+
+/// `InspectArgs` contains the path of the message to inspect, where `-` reads it from stdin.
+#[derive(Args)]
+struct InspectArgs {
+    #[arg(long)]
+    file: PathBuf,
+}
+
+/// `XtestJsArgs` contains the path of a kiwistand JS client checkout with its dependencies installed.
+#[derive(Args)]
+struct XtestJsArgs {
+    #[arg(long, env = "KIWISTAND_JS_PATH")]
+    js_path: PathBuf,
+}
+
+/// `PreviewCardArgs` contains the href of the page to preview the card of and the output format.
+#[derive(Args)]
+struct PreviewCardArgs {
+    href: String,
+    /// Print the card as an HTML snippet.
+    #[arg(long)]
+    html: bool,
+}
+
+/// `ShowArgs` contains the ID of the signature to show, or the beginning of its digest.
+#[derive(Args)]
+struct ShowArgs {
+    id: String,
+}
+
+/// `PreviewArgs` contains the href of the page to preview.
+#[derive(Args)]
+struct PreviewArgs {
+    href: String,
+}
+
 /// Returns the configuration path for the application's key store.
 ///
 /// The key store is located in the user's home directory under the ".kiwistand" folder.
+/// If no home directory can be determined, e.g. for some Windows service accounts, the function
+/// will panic.
 fn get_config_path() -> PathBuf {
-    let mut config_dir = home_dir().unwrap();
+    config_path_in(home_dir())
+}
+
+/// Returns the configuration path within the given home directory, see `get_config_path`.
+fn config_path_in(home: Option<PathBuf>) -> PathBuf {
+    let mut config_dir =
+        home.unwrap_or_else(|| fail!("Couldn't find the home directory, pass --state-dir instead"));
     config_dir.push(".kiwistand");
     config_dir
 }
 
-/// Reads the key store and returns a `LocalWallet` instance.
+/// How many passwords can be tried in a terminal before giving up.
+const PASSWORD_ATTEMPTS: usize = 3;
+
+/// Why a keystore couldn't be read.
+#[derive(Debug)]
+enum KeyError {
+    Missing,
+    NotKeystore,
+    Corrupt(WalletError),
+    WrongPassword,
+}
+
+/// Decrypts the keystore at the given path once, telling apart why that failed.
+fn decrypt_key(key_path: &Path, password: &str) -> Result<LocalWallet, KeyError> {
+    if !key_path.exists() {
+        return Err(KeyError::Missing);
+    }
+    if !is_keystore(key_path) {
+        return Err(KeyError::NotKeystore);
+    }
+    LocalWallet::decrypt_keystore(key_path, password).map_err(|error| match error {
+        // The keystore's MAC only mismatches if the password is wrong.
+        WalletError::EthKeystoreError(KeystoreError::MacMismatch) => KeyError::WrongPassword,
+        error => KeyError::Corrupt(error),
+    })
+}
+
+/// Reads the key store at the given path and returns a `LocalWallet` instance.
+///
+/// The key store is decrypted using the given password. If it's wrong and the CLI runs in a
+/// terminal, the password can be typed again. If the key store is missing, corrupt or can't be
+/// decrypted, the function will panic with a message explaining which of these happened.
+fn read_key(key_path: &Path, password: &String) -> LocalWallet {
+    let mut password = password.clone();
+    let mut attempt = 1;
+    loop {
+        match decrypt_key(key_path, &password) {
+            Ok(wallet) => return wallet,
+            Err(KeyError::Missing) => fail!(
+                "There is no keystore at {}. Create one with `key generate` or `key \
+                 import-mnemonic`, copy yours there or pass --keystore with its path",
+                key_path.display()
+            ),
+            Err(KeyError::NotKeystore) => fail!(
+                "The file at {} isn't a JSON keystore, it may be corrupt",
+                key_path.display()
+            ),
+            Err(KeyError::Corrupt(error)) => fail!(
+                "Couldn't decrypt the keystore at {}, it may be corrupt: {}",
+                key_path.display(),
+                error
+            ),
+            Err(KeyError::WrongPassword)
+                if attempt >= PASSWORD_ATTEMPTS || !io::stdin().is_terminal() =>
+            {
+                fail!("The password doesn't decrypt the keystore at {}", key_path.display())
+            }
+            Err(KeyError::WrongPassword) => {
+                attempt += 1;
+                password = prompt_hidden(&format!(
+                    "Wrong password, try again ({} of {}):",
+                    attempt, PASSWORD_ATTEMPTS
+                ));
+            }
+        }
+    }
+}
+
+/// Prints the location of every file the CLI reads or writes, and whether it exists.
+fn print_paths(context: &Context) {
+    let paths = [
+        ("state dir", context.state_dir()),
+        ("keystore", context.keystore_path()),
+        ("accounts", context.state_file("accounts.json")),
+        ("templates", context.state_file("templates.json")),
+        ("cursors", context.state_file("bridge-github.json")),
+        ("crashes", context.state_file("crash-reports")),
+        ("audit log", context.state_file("audit.jsonl")),
+        ("drafts", context.state_file("drafts.json")),
+        ("policy", context.state_file("policy.toml")),
+        ("backups", context.state_file("backups")),
+        ("lock", context.state_file("lock")),
+    ];
+    for (name, path) in paths {
+        match path {
+            Some(path) if name == "keystore" && path.exists() && !is_keystore(&path) => {
+                println!("{:<10} {} (not a keystore)", name, path.display())
+            }
+            Some(path) if path.exists() => println!("{:<10} {}", name, path.display()),
+            Some(path) => println!("{:<10} {} (missing)", name, path.display()),
+            None => println!("{:<10} (none in ephemeral mode)", name),
+        }
+    }
+}
+
+/// Prints a short introduction for running the CLI without a subcommand.
+///
+/// It points out a missing keystore, as that's what new users usually lack.
+fn print_getting_started(context: &Context) {
+    println!(
+        "kiwistand-cli {} submits and votes for links on Kiwi News.\n",
+        env!("CARGO_PKG_VERSION")
+    );
+    match context.keystore_path() {
+        Some(path) if is_keystore(&path) => {
+            println!("Signing with the keystore at {}.", path.display())
+        }
+        Some(path) => println!(
+            "There is no keystore at {} yet. Create one with `key generate`, copy yours there, \
+             pass --keystore, or use a Ledger.",
+            path.display()
+        ),
+        None => println!("No keystore is configured. Pass --keystore, or use a Ledger."),
+    }
+    println!("\nGetting started:");
+    for example in [
+        "submit --password [Password] [Link] [Title]",
+        "vote --password [Password] [Link]",
+        "submit-ledger [Link] [Title]",
+        "preview [Link]",
+    ] {
+        println!("  kiwistand-cli {}", example);
+    }
+    println!("\nRun `kiwistand-cli help` for all commands, or set KIWISTAND_DEFAULT_COMMAND");
+    println!("to the command to run when none is given, e.g. \"paths\".");
+}
+
+/// Returns whether the file at `path` looks like an encrypted JSON keystore.
+///
+/// This only checks the file's structure, decrypting it would require the password.
+fn is_keystore(path: &Path) -> bool {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return false,
+    };
+    match serde_json::from_str::<serde_json::Value>(&contents) {
+        Ok(keystore) => keystore["crypto"].is_object() || keystore["Crypto"].is_object(),
+        Err(_) => false,
+    }
+}
+
+/// Asks the user to confirm an action and returns whether they agreed.
+///
+/// The prompt is skipped when `--yes` was given or when stdin isn't a terminal, so scripted
+/// invocations keep working without changes.
+fn confirm(context: &Context, question: &str) -> bool {
+    if context.assume_yes || !io::stdin().is_terminal() {
+        return true;
+    }
+    let answer = prompt(&format!("{} [y/N]", question));
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Prints `message` and returns the line the user typed in response, without its line break.
+fn prompt(message: &str) -> String {
+    eprint!("{} ", message);
+    io::stderr().flush().expect("Couldn't flush stderr");
+    let mut answer = String::new();
+    io::stdin()
+        .read_line(&mut answer)
+        .expect("Couldn't read from stdin");
+    answer.trim_end_matches(['\r', '\n']).to_string()
+}
+
+/// Returns a command that runs `command` in the system shell, so user-supplied commands can quote
+/// their arguments, e.g. `--speak "say -v 'Good News'"`.
+fn shell(command: &str) -> Command {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C");
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c");
+        shell
+    };
+    shell.arg(command);
+    shell
+}
+
+/// Like `prompt`, but doesn't echo what the user types, for passwords and seed phrases.
 ///
-/// The key store is decrypted using the given password.
-/// If there is an issue with reading or decrypting the key store, the function will panic.
-fn read_key(password: &String) -> LocalWallet {
-    let mut key_path = get_config_path();
-    key_path.push("key");
-
-    match LocalWallet::decrypt_keystore(key_path, password) {
-        Ok(wallet) => wallet,
-        Err(_error) => panic!("Problem reading and/or decrypting the key store"),
-    }
-}
-
-// Define the EIP-712 message struct
-#[derive(Debug, Clone, Eip712, EthAbiType)]
-#[eip712(
-    name = "kiwinews",
-    version = "1.0.0",
-    salt = "kiwinews domain separator salt"
-)]
-pub struct Message {
-    pub title: String,
-    pub href: String,
-    pub r#type: String,
-    pub timestamp: U256,
-}
-
-/// Returns the current Unix time in seconds.
-fn get_unix_time() -> u64 {
-    let start = SystemTime::now();
-    let now = start
-        .duration_since(UNIX_EPOCH)
-        .expect("Time went backwards");
-    now.as_secs()
-}
-
-// Unit test
+/// Piped input isn't echoed anyway, so it's read like any other answer.
+fn prompt_hidden(message: &str) -> String {
+    if !io::stdin().is_terminal() {
+        return prompt(message);
+    }
+    rpassword::prompt_password(format!("{} ", message)).expect("Couldn't read from the terminal")
+}
+
+/// Returns the context the given global flags resolve to, for tests.
 #[cfg(test)]
-mod tests {
-    use super::*;
-    use ethers::{core::types::H160, signers::Wallet};
-
-    // Test for comparing signatures
-    #[tokio::test]
-    async fn compare_signatures() {
-        let timestamp = 1676559616;
-        let title = "hello world";
-        let href = "https://example.com";
-        let message = Message {
-            title: String::from(title),
-            href: String::from(href),
-            r#type: String::from("amplify"),
-            timestamp: U256::from(timestamp),
-        };
-        dbg!(&message);
-
-        let wallet: Wallet<SigningKey> =
-            "ad54bdeade5537fb0a553190159783e45d02d316a992db05cbed606d3ca36b39"
-                .parse()
-                .unwrap();
-        let expected: H160 = "0x0f6A79A579658E401E0B81c6dde1F2cd51d97176"
-            .parse()
-            .unwrap();
-        assert_eq!(wallet.address(), expected);
-        let signature = sign(wallet, &message).await;
-        assert_eq!(signature.to_string(), "1df128dfe1f86df4e20ecc6ebbd586e0ab56e3fc8d0db9210422c3c765633ad8793af68aa232cf39cc3f75ea18f03260258f7276c2e0d555f98e1cf16672dd201c");
+fn test_context(flags: &[&str]) -> Context {
+    let arguments = std::iter::once("kiwistand-cli").chain(flags.iter().copied());
+    Context::from_cli(&Cli::parse_from(arguments))
+}
+
+/// Returns an empty directory for a test's files, unique to the test and the process.
+#[cfg(test)]
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kiwistand-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("Couldn't create the test directory");
+    dir
+}
+
+/// Reads all of stdin and returns it with surrounding whitespace removed.
+fn read_stdin() -> String {
+    let mut input = String::new();
+    io::stdin()
+        .read_to_string(&mut input)
+        .expect("Couldn't read from stdin");
+    input.trim().to_string()
+}
+
+/// Returns the given argument, or the contents of stdin if the argument is `-`.
+fn resolve_input(value: &str) -> String {
+    if value == "-" {
+        read_stdin()
+    } else {
+        value.to_string()
+    }
+}
+
+/// Returns a URL piped into stdin, if there is one.
+///
+/// This lets an omitted href be filled in by pipelines such as `xclip -o | kiwistand-cli vote ...`.
+/// In strict mode, the href must always be given explicitly.
+fn piped_href(context: &Context) -> Option<String> {
+    if context.strict || io::stdin().is_terminal() {
+        return None;
+    }
+    let input = read_stdin();
+    if input.starts_with("https://") || input.starts_with("http://") {
+        Some(input)
+    } else {
+        None
+    }
+}
+
+/// Returns the keystore password: the one given with `--password`, or else the one from
+/// `--password-command` or `--password-file`.
+///
+/// The password is never taken from the positional arguments, so a forgotten link can't be
+/// mistaken for it, or the other way around.
+fn keystore_password(context: &Context, password: &Option<String>) -> String {
+    match password.clone().or_else(|| secret::password(context)) {
+        Some(password) => password,
+        None => fail!(
+            "The keystore password must be given with --password, --password-command or \
+             --password-file"
+        ),
+    }
+}
+
+/// Panics if both href and title ask to be read from stdin, as stdin can only be consumed once.
+fn check_single_stdin_input(href: &Option<String>, title: &Option<String>) {
+    if href.as_deref() == Some("-") && title.as_deref() == Some("-") {
+        fail!("only one of href and title can be read from stdin");
     }
 }
 
 /// Signs the given EIP-712 message with a Ledger device.
 ///
 /// The Ledger device is accessed using the provided address index.
-/// Returns the signature generated by the Ledger device.
-async fn sign_ledger(message: &Message, address_index: usize) -> Signature {
-    let ledger = Ledger::new(HDPath::LedgerLive(address_index), 1u64)
-        .await
-        .unwrap();
+/// Returns the signature generated by the Ledger device, or an error if the device couldn't be
+/// connected to.
+#[cfg(feature = "ledger")]
+async fn sign_ledger(message: &Message, address_index: usize) -> Result<Signature, LedgerError> {
+    let ledger = Ledger::new(HDPath::LedgerLive(address_index), 1u64).await?;
 
-    ledger
+    Ok(ledger
         .sign_typed_struct(message)
         .await
-        .expect("failed to sign typed data")
+        .expect("failed to sign typed data"))
 }
 
-/// Signs the given EIP-712 message with a `LocalWallet` instance.
+/// Offers to sign with the keystore after connecting to the Ledger failed.
 ///
-/// Returns the generated signature.
-async fn sign(wallet: LocalWallet, message: &Message) -> Signature {
-    wallet
-        .sign_typed_data(message)
+/// The fallback needs an explicit confirmation in the terminal, and the password from a secret
+/// provider or typed in, so scripted invocations keep failing as before. It's never offered in
+/// strict mode.
+#[cfg(feature = "ledger")]
+async fn sign_fallback(context: &Context, message: &Message, error: LedgerError) -> Signature {
+    eprintln!("Couldn't connect to the Ledger: {}", error);
+    if context.strict {
+        fail!("Problem connecting to the Ledger, and --strict rules out the keystore fallback");
+    }
+    let key_path = context.keystore();
+    let question = format!("Sign with the keystore at {} instead?", key_path.display());
+    if !io::stdin().is_terminal() || !confirm(context, &question) {
+        fail!("Problem connecting to the Ledger");
+    }
+    let password = secret::password(context).unwrap_or_else(|| prompt_hidden("Keystore password:"));
+    let start = Instant::now();
+    let wallet = read_key(&key_path, &password);
+    context.perf.record("keystore decryption", start);
+    let start = Instant::now();
+    let sig = sign(wallet, message).await;
+    context.perf.record("signing", start);
+    sig
+}
+
+/// Signs the given EIP-712 message with the Ledger at the given address index, defaulting to 0.
+///
+/// If the Ledger can't be reached, the keystore is offered instead.
+#[cfg(feature = "ledger")]
+async fn sign_with_ledger(
+    context: &Context,
+    message: &Message,
+    address_index: Option<usize>,
+) -> Signature {
+    let index = address_index.unwrap_or(0);
+    let start = Instant::now();
+    match sign_ledger(message, index).await {
+        Ok(sig) => {
+            context.perf.record("ledger signing", start);
+            sig
+        }
+        Err(error) => sign_fallback(context, message, error).await,
+    }
+}
+
+/// Panics, as this build was made without the `ledger` feature.
+#[cfg(not(feature = "ledger"))]
+async fn sign_with_ledger(
+    _context: &Context,
+    _message: &Message,
+    _address_index: Option<usize>,
+) -> Signature {
+    fail!("This build of kiwistand-cli doesn't support Ledger devices, rebuild it with the `ledger` feature")
+}
+
+/// Returns the address of the Ledger at the given address index.
+#[cfg(feature = "ledger")]
+async fn ledger_address(address_index: usize) -> Address {
+    Ledger::new(HDPath::LedgerLive(address_index), 1u64)
         .await
-        .expect("Couldn't sign message")
+        .unwrap_or_else(|error| fail!("Couldn't connect to the Ledger: {}", error))
+        .address()
+}
+
+/// Panics, as this build was made without the `ledger` feature.
+#[cfg(not(feature = "ledger"))]
+async fn ledger_address(_address_index: usize) -> Address {
+    fail!("This build of kiwistand-cli doesn't support Ledger devices, rebuild it with the `ledger` feature")
 }
 
 /// Creates a signed EIP-712 message using the provided password, href, and title.
 ///
-/// If `ledger` is true, it will sign the message using a Ledger device at the given address index.
+/// The href and title are normalized first, so whitespace and line endings don't change the
+/// signature. In strict mode, inputs that would change by normalizing them are rejected.
+/// Messages the signing policy forbids aren't signed.
+/// If `ledger` is true, it will sign the message using a Ledger device at the given address index,
+/// offering to fall back to the keystore if the device isn't available.
 /// Otherwise, it will sign the message using the local wallet decrypted with the provided password.
-/// Returns a `Value` instance containing the signed message.
+/// The user confirms the message before it's signed.
+/// Returns the signed message in its canonical JSON serialization, or `None` if it was declined.
 async fn create_message(
+    context: &Context,
     password: &String,
     href: &String,
     title: &String,
     ledger: bool,
     address_index: Option<usize>,
-) -> Value {
-    let timestamp = get_unix_time();
+) -> Option<String> {
+    if context.strict {
+        if let Some(problem) = normalize::problem(href, title) {
+            fail!("{}", problem);
+        }
+    }
+    let href = normalize::href(href);
+    let title = preflight::preflight(context, &href, &normalize::title(title));
+    policy::check(context, &href, &title);
+    let timestamp = context.clock.now();
     let message = Message {
-        title: String::from(title),
-        href: String::from(href),
+        title,
+        href,
         r#type: String::from("amplify"),
         timestamp: U256::from(timestamp),
     };
+    // Confirmed before signing, so declined messages are neither signed nor logged.
+    speak::speak(context, &speak::summary(&message));
+    if !confirm(context, "Sign and send this message to news.kiwistand.com?") {
+        eprintln!("Aborted, nothing was signed");
+        speak::speak(context, "Aborted, nothing was signed.");
+        return None;
+    }
     let sig = if ledger {
-        let index = address_index.unwrap_or(0);
-        sign_ledger(&message, index).await
+        sign_with_ledger(context, &message, address_index).await
     } else {
-        let wallet = read_key(password);
-        sign(wallet, &message).await
+        let start = Instant::now();
+        let wallet = read_key(&context.keystore(), password);
+        context.perf.record("keystore decryption", start);
+        let start = Instant::now();
+        let sig = sign(wallet, &message).await;
+        context.perf.record("signing", start);
+        sig
     };
-    // TODO: We should actually test this signature against the signature
-    // from JS and make sure they're equal.
-    let body = json!({
-        "title": message.title,
-        "href": message.href,
-        "type": message.r#type,
-        "timestamp": timestamp,
-        "signature": format!("0x{}", sig),
-    });
-
-    body
+    audit::record(context, &message, &sig);
+    eprintln!("Signed message {}", message.id());
+    Some(canonical::serialize(&message, &sig))
 }
 
 /// Sends the signed EIP-712 message to the Kiwistand server.
 ///
-/// The message is sent as a JSON payload in an HTTP POST request.
-/// If the request fails, the function will panic.
-async fn send(message: Value) {
-    let client = reqwest::Client::new();
+/// The message is sent as a JSON payload in an HTTP POST request, the user confirmed it before
+/// it was signed. Returns whether the server accepted the message. If the request fails, the
+/// function will panic.
+async fn send(context: &Context, message: String) -> bool {
     dbg!(&message);
-    let result = client
-        .post("https://news.kiwistand.com/messages")
-        .json(&message)
-        .send()
+    let result = context
+        .transport
+        .post_json("https://news.kiwistand.com/messages", message)
         .await;
 
     let response = match result {
         Ok(response) => response,
-        Err(_error) => panic!("Failed sending message"),
+        Err(_error) => fail!("Failed sending message"),
     };
-    let body = response.text().await;
-    if let Err(e) = dbg!(body) {
-        eprintln!("Error: {:?}", e);
+    dbg!(&response.body);
+    if response.is_success() {
+        speak::speak(context, "The message was sent and accepted.");
+    } else {
+        speak::speak(context, "The message was sent, but the node rejected it.");
     }
+    response.is_success()
 }
 
 /// The entry point of the application.
@@ -236,70 +825,274 @@ async fn send(message: Value) {
 /// Parses command-line arguments and calls the appropriate subcommand functions based on user input.
 #[tokio::main]
 async fn main() {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
+    if cli.command.is_none() {
+        if let Ok(default) = std::env::var("KIWISTAND_DEFAULT_COMMAND") {
+            let default = default.split_whitespace().map(String::from);
+            cli = Cli::parse_from(std::env::args().chain(default));
+        }
+    }
+    let context = Context::from_cli(&cli);
+    report::install_panic_hook(&context);
+    shutdown::handle_signals(context.state_dir());
+    backup::auto(&context);
 
     match &cli.command {
+        Some(command) => run(&context, command).await,
+        None => print_getting_started(&context),
+    }
+    context.perf.report();
+}
+
+/// Runs the given subcommand.
+async fn run(context: &Context, command: &Commands) {
+    match command {
         // Submit a news item with the given href and title
         Commands::Submit(args) => {
-            let password = match &args.password {
-                Some(password) => password,
-                None => panic!("password must be provided"),
-            };
+            let password = keystore_password(context, &args.password);
+            check_single_stdin_input(&args.href, &args.title);
+            if let Some(path) = &args.extract_links {
+                links::submit_extracted(context, &password, path).await;
+                return;
+            }
             let href = match &args.href {
-                Some(href) => href,
-                None => panic!("href must be provided"),
-            };
-            let title = match &args.title {
-                Some(title) => title,
-                None => panic!("title must be provided"),
+                Some(href) => resolve_input(href),
+                None => piped_href(context).unwrap_or_else(|| fail!("href must be provided")),
             };
+            let title = args.title.as_deref().map(resolve_input);
+            let title = template::apply(context, &args.template, title);
+            let title = transform::apply(context, &args.transform, title);
             let ledger = false;
-            let message = create_message(password, href, title, ledger, None).await;
-            send(message).await;
+            if let Some(message) =
+                create_message(context, &password, &href, &title, ledger, None).await
+            {
+                send(context, message).await;
+            }
         }
         // Vote for a news item with the given href
         Commands::Vote(args) => {
-            let password = match &args.password {
-                Some(password) => password,
-                None => panic!("password must be provided"),
-            };
+            let password = keystore_password(context, &args.password);
             let href = match &args.href {
-                Some(href) => href,
-                None => panic!("href must be provided"),
+                Some(href) => resolve_input(href),
+                None => piped_href(context).unwrap_or_else(|| fail!("href must be provided")),
             };
             let ledger = false;
             let title = String::new(); // Empty title
-            let message = create_message(password, href, &title, ledger, None).await;
-            send(message).await;
+            if let Some(message) =
+                create_message(context, &password, &href, &title, ledger, None).await
+            {
+                send(context, message).await;
+            }
         }
         // Submit a news item using a Ledger device
+        #[cfg(feature = "ledger")]
         Commands::SubmitLedger(args) => {
+            check_single_stdin_input(&args.href, &args.title);
             let href = match &args.href {
-                Some(href) => href,
-                None => panic!("href must be provided"),
-            };
-            let title = match &args.title {
-                Some(title) => title,
-                None => panic!("title must be provided"),
+                Some(href) => resolve_input(href),
+                None => piped_href(context).unwrap_or_else(|| fail!("href must be provided")),
             };
+            let title = args.title.as_deref().map(resolve_input);
+            let title = template::apply(context, &args.template, title);
+            let title = transform::apply(context, &args.transform, title);
             let ledger = true;
             let password = String::new();
             let address_index = args.address_index;
-            let message = create_message(&password, href, title, ledger, address_index).await;
-            send(message).await;
+            if let Some(message) =
+                create_message(context, &password, &href, &title, ledger, address_index).await
+            {
+                send(context, message).await;
+            }
         }
         // Vote for a news item using a Ledger device
+        #[cfg(feature = "ledger")]
         Commands::VoteLedger(args) => {
             let href = match &args.href {
-                Some(href) => href,
-                None => panic!("href must be provided"),
+                Some(href) => resolve_input(href),
+                None => piped_href(context).unwrap_or_else(|| fail!("href must be provided")),
             };
             let ledger = true;
             let password = String::new();
             let title = String::new(); // Empty title
             let address_index = args.address_index;
-            let message = create_message(&password, href, &title, ledger, address_index).await;
-            send(message).await;
+            if let Some(message) =
+                create_message(context, &password, &href, &title, ledger, address_index).await
+            {
+                send(context, message).await;
+            }
+        }
+        // Print the built-in explanation of a protocol topic
+        Commands::Explain(args) => explain::explain(&args.topic),
+        // Inspect a message and fail if anything about it is malformed
+        Commands::Inspect(args) => {
+            if !inspect::inspect(context, &args.file) {
+                std::process::exit(1);
+            }
         }
+        // Show where the CLI keeps its files
+        Commands::Paths => print_paths(context),
+        // Manage title templates
+        Commands::Template(args) => template::run(context, args),
+        // Announce events from other services
+        Commands::Bridge(args) => bridge::run(context, args).await,
+        // Show a linked page in the terminal
+        Commands::Preview(args) => preview::preview(context, &args.href).await,
+        // Show the card a link would be displayed with
+        Commands::PreviewCard(args) => preview::preview_card(context, &args.href, args.html).await,
+        // Compare payloads with another client and fail on any difference
+        Commands::Compat(args) => {
+            if !compat::run(context, args).await {
+                std::process::exit(1);
+            }
+        }
+        // Create or manage the keystore
+        Commands::Key(args) => key::run(context, args),
+        // Show which address signs, before submitting with it
+        Commands::Whoami(args) => {
+            let address = if args.ledger {
+                let index = args.address_index.unwrap_or(0);
+                eprintln!("Signing with the Ledger at address index {}", index);
+                ledger_address(index).await
+            } else {
+                let key_path = context.keystore();
+                eprintln!("Signing with the keystore at {}", key_path.display());
+                let password = args
+                    .password
+                    .clone()
+                    .or_else(|| secret::password(context))
+                    .unwrap_or_else(|| prompt_hidden("Keystore password:"));
+                read_key(&key_path, &password).address()
+            };
+            println!("{}", to_checksum(&address, None));
+        }
+        // Run the self-test and fail if any step failed
+        Commands::Selftest(args) => {
+            if !selftest::run(context, args).await {
+                std::process::exit(1);
+            }
+        }
+        // Manage and submit drafts
+        Commands::Draft(args) => draft::run(context, args).await,
+        // Back up or restore the state files
+        Commands::Backup(args) => backup::run(context, args),
+        // Show a logged signature and fail if it can't be found
+        Commands::Show(args) => {
+            if !audit::show(context, &args.id) {
+                std::process::exit(1);
+            }
+        }
+        // Verify the audit log and fail if it was tampered with
+        Commands::Audit(args) => {
+            if !audit::run(context, args) {
+                std::process::exit(1);
+            }
+        }
+        // Cross-check signing against the JS reference client
+        Commands::XtestJs(args) => {
+            if !xtest_js::run(&args.js_path).await {
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(value: &str) -> Option<String> {
+        Some(String::from(value))
+    }
+
+    #[test]
+    fn tells_apart_why_keystores_cannot_be_read() {
+        let dir = test_dir("read-key");
+        let path = dir.join("key");
+        assert!(matches!(decrypt_key(&path, "secret"), Err(KeyError::Missing)));
+
+        std::fs::write(&path, "not a keystore").unwrap();
+        assert!(matches!(decrypt_key(&path, "secret"), Err(KeyError::NotKeystore)));
+
+        std::fs::write(&path, r#"{"crypto":{"cipher":"aes-128-ctr"}}"#).unwrap();
+        assert!(matches!(decrypt_key(&path, "secret"), Err(KeyError::Corrupt(_))));
+
+        std::fs::remove_file(&path).unwrap();
+        let (wallet, _) =
+            LocalWallet::new_keystore(&dir, &mut rand::thread_rng(), "secret", Some("key"))
+                .unwrap();
+        assert!(matches!(decrypt_key(&path, "wrong"), Err(KeyError::WrongPassword)));
+        let decrypted = decrypt_key(&path, "secret").unwrap();
+        assert_eq!(decrypted.address(), wallet.address());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn never_takes_the_password_from_the_arguments() {
+        let cli = Cli::parse_from([
+            "kiwistand-cli",
+            "--password-command",
+            "echo secret",
+            "submit",
+            "https://example.com",
+            "--template",
+            "release",
+        ]);
+        let context = Context::from_cli(&cli);
+        match &cli.command {
+            Some(Commands::Submit(args)) => {
+                assert_eq!(args.href, some("https://example.com"));
+                assert_eq!(args.title, None);
+                assert_eq!(keystore_password(&context, &args.password), "secret");
+            }
+            _ => panic!("parsed another command"),
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn leaves_the_link_of_piped_votes_to_stdin() {
+        let cli = Cli::parse_from([
+            "kiwistand-cli",
+            "--password-command",
+            "echo secret",
+            "vote",
+            "--password",
+            "pw",
+        ]);
+        let context = Context::from_cli(&cli);
+        match &cli.command {
+            Some(Commands::Vote(args)) => {
+                assert_eq!(args.href, None);
+                assert_eq!(keystore_password(&context, &args.password), "pw");
+            }
+            _ => panic!("parsed another command"),
+        }
+    }
+
+    #[test]
+    fn keeps_the_state_in_the_home_directory() {
+        let home = std::env::temp_dir().join("home");
+        assert_eq!(config_path_in(Some(home.clone())), home.join(".kiwistand"));
+        assert_eq!(config_path_in(home_dir()), get_config_path());
+    }
+
+    #[test]
+    #[should_panic(expected = "pass --state-dir instead")]
+    fn explains_a_missing_home_directory() {
+        config_path_in(None);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn resolves_windows_paths() {
+        let state_dir = config_path_in(Some(PathBuf::from(r"C:\Users\kiwi")));
+        assert_eq!(state_dir, Path::new(r"C:\Users\kiwi\.kiwistand"));
+        let context = test_context(&["--state-dir", state_dir.to_str().unwrap()]);
+        assert_eq!(context.keystore(), Path::new(r"C:\Users\kiwi\.kiwistand\key"));
+        let context = test_context(&["--keystore", r"D:\keys\kiwi"]);
+        let keystore = context.keystore();
+        assert_eq!(keystore.parent(), Some(Path::new(r"D:\keys")));
+        assert_eq!(keystore.file_name().unwrap(), "kiwi");
     }
 }
@@ -0,0 +1,56 @@
+// @format
+use kiwistand_api::Observer;
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `Profiler` collects how long the phases of an invocation took, for `--profile-perf`.
+pub struct Profiler {
+    enabled: bool,
+    started: Instant,
+    phases: Mutex<Vec<(String, Duration)>>,
+}
+
+impl Profiler {
+    pub fn new(enabled: bool) -> Profiler {
+        Profiler {
+            enabled,
+            started: Instant::now(),
+            phases: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Records that `phase` ran from `start` until now.
+    pub fn record(&self, phase: impl Into<String>, start: Instant) {
+        if self.enabled {
+            let elapsed = start.elapsed();
+            self.phases.lock().unwrap().push((phase.into(), elapsed));
+        }
+    }
+
+    /// Prints the recorded phases and the total run time to stderr, if profiling is enabled.
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+        let phases = self.phases.lock().unwrap();
+        let width = phases
+            .iter()
+            .map(|(phase, _)| phase.len())
+            .chain(Some("total".len()))
+            .max()
+            .unwrap_or_default();
+        eprintln!();
+        for (phase, elapsed) in phases.iter() {
+            eprintln!("{:<width$}  {:>9.3}s", phase, elapsed.as_secs_f64(), width = width);
+        }
+        let total = self.started.elapsed();
+        eprintln!("{:<width$}  {:>9.3}s", "total", total.as_secs_f64(), width = width);
+    }
+}
+
+impl Observer for Profiler {
+    fn request_finished(&self, request: String, start: Instant) {
+        self.record(request, start);
+    }
+}
@@ -0,0 +1,199 @@
+// @format
+use serde::Deserialize;
+
+use std::fs;
+
+use crate::{audit, Context};
+
+/// Returns the host of `href` in lowercase, if it has one.
+fn host(href: &str) -> Option<String> {
+    reqwest::Url::parse(href)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+}
+
+/// Returns whether `host` is `domain` or one of its subdomains.
+fn matches_domain(host: &str, domain: &str) -> bool {
+    let domain = domain.to_lowercase();
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+/// The rules of the signing policy, each of which is optional.
+///
+/// Unknown keys are rejected, so that a misspelled rule can't silently stop applying.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Policy {
+    blocked_domains: Option<Vec<String>>,
+    allowed_domains: Option<Vec<String>>,
+    max_submissions_per_day: Option<u64>,
+    /// The UTC hours from which and until which nothing is signed.
+    quiet_hours: Option<[u64; 2]>,
+}
+
+/// Returns every rule of `policy` that signing a message for `href` would break.
+///
+/// `submissions` is the number of submissions signed within the last day, which only counts if
+/// this message is a submission too.
+fn violations(
+    policy: &Policy,
+    href: &str,
+    is_submission: bool,
+    now: u64,
+    submissions: usize,
+) -> Vec<String> {
+    let mut violations = Vec::new();
+    let host = host(href).unwrap_or_default();
+    if let Some(blocked) = &policy.blocked_domains {
+        if let Some(domain) = blocked.iter().find(|domain| matches_domain(&host, domain)) {
+            violations.push(format!("{} is a blocked domain", domain));
+        }
+    }
+    if let Some(allowed) = &policy.allowed_domains {
+        if !allowed.iter().any(|domain| matches_domain(&host, domain)) {
+            violations.push(format!("{} isn't an allowed domain", host));
+        }
+    }
+    if let Some(max) = policy.max_submissions_per_day {
+        if is_submission && submissions as u64 >= max {
+            violations.push(format!(
+                "{} submissions were signed within the last day, the maximum is {}",
+                submissions, max
+            ));
+        }
+    }
+    if let Some([start, end]) = policy.quiet_hours {
+        let hour = now % (24 * 60 * 60) / (60 * 60);
+        let quiet = if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        };
+        if quiet {
+            violations.push(format!(
+                "it's {}:00 UTC, within the quiet hours from {}:00 to {}:00",
+                hour, start, end
+            ));
+        }
+    }
+    violations
+}
+
+/// Parses the TOML policy, panicking if it isn't valid, so that a broken policy never lets a
+/// message through.
+fn parse(contents: &str) -> Policy {
+    let policy: Policy = toml::from_str(contents)
+        .unwrap_or_else(|error| fail!("Couldn't parse the signing policy: {}", error));
+    if let Some(hours) = policy.quiet_hours {
+        if hours.iter().any(|hour| *hour > 23) {
+            fail!("Couldn't parse the signing policy: quiet_hours must be two hours from 0 to 23");
+        }
+    }
+    policy
+}
+
+/// Panics if signing a message for `href` breaks a rule of the signing policy.
+///
+/// The policy is read from `policy.toml` in the state directory, if it exists, and ignored when
+/// `--override-policy` was given.
+pub fn check(context: &Context, href: &str, title: &str) {
+    if context.override_policy {
+        return;
+    }
+    let path = match context.state_file("policy.toml") {
+        Some(path) if path.exists() => path,
+        _ => return,
+    };
+    let contents = fs::read_to_string(&path).expect("Couldn't read the signing policy");
+    let policy = parse(&contents);
+
+    let now = context.clock.now();
+    let is_submission = !title.is_empty();
+    let since = now.saturating_sub(24 * 60 * 60);
+    let submissions = match audit::submissions_since(context, since) {
+        Some(submissions) => submissions,
+        // Without an audit log every day would look empty, so the limit would never apply.
+        None if is_submission && policy.max_submissions_per_day.is_some() => fail!(
+            "The signing policy in {} limits submissions per day, which can't be enforced without \
+             the audit log. Pass --state-dir to keep one, or --override-policy to sign anyway",
+            path.display()
+        ),
+        None => 0,
+    };
+    let violations = violations(&policy, href, is_submission, now, submissions);
+    if !violations.is_empty() {
+        fail!(
+            "The signing policy in {} forbids this: {}. Pass --override-policy to sign anyway",
+            path.display(),
+            violations.join(", ")
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::FixedClock;
+    use crate::{test_context, test_dir};
+
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn parses_toml_policies() {
+        let policy = parse("blocked_domains = [\"example.com\"]\nquiet_hours = [22, 6]\n");
+        assert_eq!(policy.blocked_domains, Some(vec![String::from("example.com")]));
+        assert_eq!(policy.quiet_hours, Some([22, 6]));
+        assert_eq!(policy.allowed_domains, None);
+    }
+
+    #[test]
+    fn rejects_invalid_policies() {
+        for contents in [
+            "blocked_domain = [\"example.com\"]\n",
+            "max_submissions_per_day = \"10\"\n",
+            "quiet_hours = [\"22\", \"6\"]\n",
+            "quiet_hours = [22]\n",
+            "quiet_hours = [22, 24]\n",
+        ] {
+            assert!(panic::catch_unwind(|| parse(contents)).is_err(), "{}", contents);
+        }
+    }
+
+    #[test]
+    fn checks_domains() {
+        let policy = parse("blocked_domains = [\"example.com\"]");
+        assert_eq!(violations(&policy, "https://news.example.com/a", true, 0, 0).len(), 1);
+        assert!(violations(&policy, "https://notexample.com", true, 0, 0).is_empty());
+
+        let policy = parse("allowed_domains = [\"github.com\"]");
+        assert!(violations(&policy, "https://github.com/attestate", true, 0, 0).is_empty());
+        assert_eq!(violations(&policy, "https://example.com", true, 0, 0).len(), 1);
+    }
+
+    #[test]
+    fn checks_limits_and_quiet_hours() {
+        let policy = parse("max_submissions_per_day = 2\nquiet_hours = [22, 6]");
+        let noon = 12 * 60 * 60;
+        assert!(violations(&policy, "https://example.com", true, noon, 1).is_empty());
+        assert_eq!(violations(&policy, "https://example.com", true, noon, 2).len(), 1);
+        assert!(violations(&policy, "https://example.com", false, noon, 2).is_empty());
+        let night = 23 * 60 * 60;
+        assert_eq!(violations(&policy, "https://example.com", false, night, 0).len(), 1);
+    }
+
+    #[test]
+    fn checks_quiet_hours_at_the_current_time() {
+        let dir = test_dir("policy-quiet-hours");
+        fs::write(dir.join("policy.toml"), "quiet_hours = [22, 6]\n").unwrap();
+        let mut context = test_context(&["--state-dir", dir.to_str().unwrap()]);
+        context.clock = Box::new(FixedClock(12 * 60 * 60));
+        check(&context, "https://example.com", "");
+
+        context.clock = Box::new(FixedClock(23 * 60 * 60));
+        let night = panic::catch_unwind(AssertUnwindSafe(|| {
+            check(&context, "https://example.com", "");
+        }));
+        assert!(night.is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
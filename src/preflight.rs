@@ -0,0 +1,79 @@
+// @format
+use kiwistand_core::limits::{truncate, MAX_HREF_LENGTH, MAX_TITLE_LENGTH};
+
+use std::io::{self, IsTerminal};
+
+use crate::{confirm, Context};
+
+/// Checks the href and title against the node limits before signing and returns the title to use.
+///
+/// An href that isn't an http(s) link, or is overlong, can't be fixed and panics. An overlong
+/// title is truncated once the user confirmed it, except in strict mode, where it panics as
+/// well. If the confirmation is skipped, the truncated title is printed instead, so it never
+/// changes silently.
+pub fn preflight(context: &Context, href: &str, title: &str) -> String {
+    let href_length = href.chars().count();
+    if href_length > MAX_HREF_LENGTH {
+        fail!(
+            "The href is {} characters long, but nodes accept at most {}",
+            href_length, MAX_HREF_LENGTH
+        );
+    }
+    // The href isn't echoed, as it may be a password given in the wrong place.
+    let is_link =
+        reqwest::Url::parse(href).is_ok_and(|url| matches!(url.scheme(), "http" | "https"));
+    if !is_link {
+        fail!("The href isn't an http(s) link, so nothing was signed");
+    }
+    let title_length = title.chars().count();
+    if title_length <= MAX_TITLE_LENGTH {
+        return title.to_string();
+    }
+
+    if context.strict {
+        fail!(
+            "The title is {} characters long, but nodes accept at most {}",
+            title_length, MAX_TITLE_LENGTH
+        );
+    }
+    let truncated = truncate(title, MAX_TITLE_LENGTH);
+    eprintln!(
+        "The title is {} characters long, but nodes accept at most {}.",
+        title_length, MAX_TITLE_LENGTH
+    );
+    if context.assume_yes || !io::stdin().is_terminal() {
+        eprintln!("Submitting it as {:?}.", truncated);
+    } else if !confirm(context, &format!("Submit it as {:?}?", truncated)) {
+        fail!("The title exceeds the limit of {} characters", MAX_TITLE_LENGTH);
+    }
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_context;
+
+    #[test]
+    fn truncates_overlong_titles() {
+        let context = test_context(&["--yes"]);
+        let title = "a".repeat(MAX_TITLE_LENGTH + 1);
+        let truncated = preflight(&context, "https://example.com", &title);
+        assert_eq!(truncated, truncate(&title, MAX_TITLE_LENGTH));
+        assert_eq!(preflight(&context, "https://example.com", "short"), "short");
+    }
+
+    #[test]
+    #[should_panic(expected = "nodes accept at most")]
+    fn rejects_overlong_titles_in_strict_mode() {
+        let context = test_context(&["--yes", "--strict"]);
+        preflight(&context, "https://example.com", &"a".repeat(MAX_TITLE_LENGTH + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "isn't an http(s) link")]
+    fn rejects_hrefs_that_are_not_links() {
+        let context = test_context(&["--yes"]);
+        preflight(&context, "hunter2", "");
+    }
+}
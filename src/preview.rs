@@ -0,0 +1,98 @@
+// @format
+use std::env;
+use std::io::{self, IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+use crate::{html, Context};
+
+/// The column at which the extracted text is wrapped.
+const WIDTH: usize = 80;
+
+/// Fetches the page at `href` and shows its readable text, in a pager when run in a terminal.
+pub async fn preview(context: &Context, href: &str) {
+    let response = context
+        .transport
+        .get_page(href)
+        .await
+        .expect("Failed fetching the page");
+    if !response.is_success() {
+        fail!("The page responded with {}", response.status);
+    }
+
+    let title = html::title(&response.body).unwrap_or_else(|| String::from(href));
+    let text = html::wrap(&html::readable_text(&response.body), WIDTH);
+    let page = format!("{}\n{}\n\n{}\n", html::wrap(&title, WIDTH), href, text);
+    if !io::stdout().is_terminal() || !show_in_pager(&page) {
+        print!("{}", page);
+    }
+}
+
+/// Fetches the page at `href` and prints the card the link would be shown with, from its
+/// OpenGraph metadata: as labeled lines, or as an HTML snippet if `as_html` is set.
+pub async fn preview_card(context: &Context, href: &str, as_html: bool) {
+    let response = context
+        .transport
+        .get_page(href)
+        .await
+        .expect("Failed fetching the page");
+    if !response.is_success() {
+        fail!("The page responded with {}", response.status);
+    }
+
+    let page = &response.body;
+    let title = html::meta(page, "og:title")
+        .or_else(|| html::title(page))
+        .unwrap_or_else(|| String::from(href));
+    let domain = reqwest::Url::parse(href)
+        .ok()
+        .and_then(|url| url.host_str().map(String::from))
+        .map(|host| host.trim_start_matches("www.").to_string())
+        .unwrap_or_default();
+    let description = html::meta(page, "og:description").or_else(|| html::meta(page, "description"));
+    let image = html::meta(page, "og:image");
+
+    if as_html {
+        println!("<div class=\"kiwistand-card\">");
+        if let Some(image) = &image {
+            println!("  <img src=\"{}\" alt=\"\">", html::escape(image));
+        }
+        println!(
+            "  <a href=\"{}\">{}</a> <span>({})</span>",
+            html::escape(href),
+            html::escape(&title),
+            html::escape(&domain)
+        );
+        if let Some(description) = &description {
+            println!("  <p>{}</p>", html::escape(description));
+        }
+        println!("</div>");
+    } else {
+        println!("{:<12} {}", "title", title);
+        println!("{:<12} {}", "domain", domain);
+        println!("{:<12} {}", "image", image.as_deref().unwrap_or("(none)"));
+        if let Some(description) = &description {
+            println!("{:<12} {}", "description", description);
+        }
+    }
+}
+
+/// Shows `text` in the pager from `$PAGER`, or `less`, and returns false if it couldn't be run.
+fn show_in_pager(text: &str) -> bool {
+    let pager = env::var("PAGER").unwrap_or_else(|_| String::from("less"));
+    let mut parts = pager.split_whitespace();
+    let program = match parts.next() {
+        Some(program) => program,
+        None => return false,
+    };
+    let mut child = match Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    // The pager may quit before reading everything, which isn't an error.
+    let _ = child.stdin.take().unwrap().write_all(text.as_bytes());
+    child.wait().is_ok()
+}
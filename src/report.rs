@@ -0,0 +1,301 @@
+// @format
+use clap::{Command, CommandFactory};
+use dirs::home_dir;
+use kiwistand_api::atomic;
+
+use std::backtrace::Backtrace;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::fs;
+use std::panic::{self, PanicHookInfo};
+use std::path::{Path, PathBuf};
+
+use crate::clock::{Clock, SystemClock};
+use crate::{Cli, Context};
+
+/// Where users should report bugs.
+const ISSUES_URL: &str = "https://github.com/attestate/kiwistand-cli/issues";
+
+/// How many of the newest crash reports are kept.
+const KEEP_REPORTS: usize = 10;
+
+thread_local! {
+    /// Whether the next panic on this thread is a failure raised with `fail!`.
+    static EXPECTED_FAILURE: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Marks the next panic on this thread as the user's to fix, so no report is written for it.
+pub fn expect_failure() {
+    EXPECTED_FAILURE.with(|expected| expected.set(true));
+}
+
+/// Installs a panic hook that prints the failure briefly and, unless it was raised with `fail!`,
+/// writes a diagnostic report.
+///
+/// The report contains the version, OS, redacted arguments and message, a summary of the state
+/// files and a backtrace. It's written to `crash-reports` in the state directory, or to the
+/// temporary directory in ephemeral mode, and only the newest `KEEP_REPORTS` are kept.
+pub fn install_panic_hook(context: &Context) {
+    let report_dir = context
+        .state_file("crash-reports")
+        .unwrap_or_else(|| std::env::temp_dir().join("kiwistand-cli"));
+    let summary = state_summary(context);
+    let arguments = redacted_arguments(&std::env::args().skip(1).collect::<Vec<String>>());
+
+    panic::set_hook(Box::new(move |info| {
+        let message = panic_message(info);
+        eprintln!("Error: {}", message);
+        if EXPECTED_FAILURE.with(|expected| expected.replace(false)) {
+            return;
+        }
+
+        let report = format!(
+            "kiwistand-cli {}\nos: {} {}\narguments: {}\n{}\npanic: {}\nlocation: {}\n\nbacktrace:\n{}\n",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS,
+            std::env::consts::ARCH,
+            arguments,
+            summary,
+            redacted_message(&message),
+            info.location()
+                .map(|location| location.to_string())
+                .unwrap_or_default(),
+            Backtrace::force_capture(),
+        );
+        let path = report_dir.join(format!("crash-{}.txt", SystemClock.now()));
+        if atomic::write(&path, report).is_ok() {
+            remove_old_reports(&report_dir);
+            eprintln!(
+                "A diagnostic report was written to {}. If this looks like a bug, please attach it to an issue at {}",
+                path.display(),
+                ISSUES_URL
+            );
+        }
+    }));
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(info: &PanicHookInfo) -> String {
+    if let Some(message) = info.payload().downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = info.payload().downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown failure")
+    }
+}
+
+/// Returns the time a crash report was written at, from its name, if it is one.
+fn report_time(name: &str) -> Option<u64> {
+    name.strip_prefix("crash-")?
+        .strip_suffix(".txt")?
+        .parse()
+        .ok()
+}
+
+/// Deletes all but the newest `KEEP_REPORTS` crash reports in `dir`.
+fn remove_old_reports(dir: &Path) {
+    let mut reports: Vec<(u64, PathBuf)> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let time = report_time(&entry.file_name().to_string_lossy())?;
+                Some((time, entry.path()))
+            })
+            .collect(),
+        Err(_) => return,
+    };
+    reports.sort();
+    for (_, path) in &reports[..reports.len().saturating_sub(KEEP_REPORTS)] {
+        let _ = fs::remove_file(path);
+    }
+}
+
+/// Returns the panic message with the home directory abbreviated to `~`, and quoted strings and
+/// links replaced, as those can be titles, hrefs, paths or commands.
+fn redacted_message(message: &str) -> String {
+    let message = match home_dir().map(|home| home.display().to_string()) {
+        Some(home) if !home.is_empty() => message.replace(&home, "~"),
+        _ => message.to_string(),
+    };
+    let mut unquoted = String::new();
+    let mut chars = message.chars();
+    while let Some(c) = chars.next() {
+        unquoted.push(c);
+        if c == '"' {
+            // Skips to the closing quote, which Debug formatting escapes within the string.
+            let mut escaped = false;
+            for c in chars.by_ref() {
+                match c {
+                    '"' if !escaped => break,
+                    '\\' => escaped = !escaped,
+                    _ => escaped = false,
+                }
+            }
+            unquoted.push_str("<redacted>\"");
+        }
+    }
+    unquoted
+        .split(' ')
+        .map(|word| {
+            if word.starts_with("http://") || word.starts_with("https://") {
+                "<redacted>"
+            } else {
+                word
+            }
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Returns the flags of `command` and its subcommands that take a value, e.g. `--keystore`.
+fn flags_with_values(command: &Command) -> HashSet<String> {
+    let mut flags = HashSet::new();
+    for argument in command.get_arguments() {
+        if argument.is_positional() || !argument.get_action().takes_values() {
+            continue;
+        }
+        if let Some(long) = argument.get_long() {
+            flags.insert(format!("--{}", long));
+        }
+        if let Some(short) = argument.get_short() {
+            flags.insert(format!("-{}", short));
+        }
+    }
+    for subcommand in command.get_subcommands() {
+        flags.extend(flags_with_values(subcommand));
+    }
+    flags
+}
+
+/// Returns the command-line arguments with every value replaced, keeping the flags and the
+/// subcommand, so passwords, titles, links and paths don't end up in reports.
+///
+/// The value following a flag that takes one, e.g. `--keystore <path>`, is replaced too, so it
+/// isn't mistaken for the subcommand.
+fn redacted_arguments(arguments: &[String]) -> String {
+    let flags = flags_with_values(&Cli::command());
+    let mut subcommand_seen = false;
+    let mut value_expected = false;
+    arguments
+        .iter()
+        .map(|argument| {
+            if value_expected {
+                value_expected = false;
+                String::from("<redacted>")
+            } else if !argument.starts_with('-') {
+                if subcommand_seen {
+                    String::from("<redacted>")
+                } else {
+                    subcommand_seen = true;
+                    argument.clone()
+                }
+            } else if let Some((flag, _)) = argument.split_once('=') {
+                format!("{}=<redacted>", flag)
+            } else {
+                value_expected = flags.contains(argument);
+                argument.clone()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Returns which state files exist, with the home directory abbreviated to `~`.
+fn state_summary(context: &Context) -> String {
+    let paths = [
+        ("state dir", context.state_dir()),
+        ("keystore", context.keystore_path()),
+    ];
+    paths
+        .iter()
+        .map(|(name, path)| match path {
+            Some(path) => format!(
+                "{}: {} ({})",
+                name,
+                redact_home(path),
+                if path.exists() { "exists" } else { "missing" }
+            ),
+            None => format!("{}: none", name),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Returns `path` with the home directory replaced by `~`.
+fn redact_home(path: &Path) -> String {
+    match home_dir().and_then(|home| path.strip_prefix(home).ok().map(PathBuf::from)) {
+        Some(relative) => format!("~/{}", relative.display()),
+        None => path.display().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redacted(arguments: &[&str]) -> String {
+        let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+        redacted_arguments(&arguments)
+    }
+
+    #[test]
+    fn redacts_values_of_flags() {
+        assert_eq!(
+            redacted(&["--keystore", "/home/me/key", "submit", "secret", "https://example.com"]),
+            "--keystore <redacted> submit <redacted> <redacted>"
+        );
+        assert_eq!(
+            redacted(&["--password-file=/home/me/pw", "--yes", "vote", "https://example.com"]),
+            "--password-file=<redacted> --yes vote <redacted>"
+        );
+        assert_eq!(
+            redacted(&["submit", "--template", "release", "secret"]),
+            "submit --template <redacted> <redacted>"
+        );
+    }
+
+    #[test]
+    fn redacts_messages() {
+        assert_eq!(
+            redacted_message("The password command \"pass show \\\"kiwi\\\"\" failed"),
+            "The password command \"<redacted>\" failed"
+        );
+        assert_eq!(
+            redacted_message("Fixture 0001.json was recorded for GET https://example.com/a"),
+            "Fixture 0001.json was recorded for GET <redacted>"
+        );
+        let home = home_dir().unwrap().join("key");
+        assert_eq!(
+            redacted_message(&format!("There is no keystore at {}", home.display())),
+            format!("There is no keystore at ~{}key", std::path::MAIN_SEPARATOR)
+        );
+    }
+
+    #[test]
+    fn keeps_the_newest_reports() {
+        let dir = crate::test_dir("report-retention");
+        for time in 1..=12 {
+            fs::write(dir.join(format!("crash-{}.txt", time)), "").unwrap();
+        }
+        fs::write(dir.join("notes.txt"), "").unwrap();
+        remove_old_reports(&dir);
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), KEEP_REPORTS + 1);
+        assert!(names.contains(&String::from("notes.txt")));
+        assert!(!names.contains(&String::from("crash-2.txt")));
+        assert!(names.contains(&String::from("crash-3.txt")));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn marks_expected_failures() {
+        let failure = panic::catch_unwind(|| fail!("The passwords don't match"));
+        assert!(failure.is_err());
+        assert!(EXPECTED_FAILURE.with(|expected| expected.replace(false)));
+    }
+}
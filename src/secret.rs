@@ -0,0 +1,101 @@
+// @format
+use std::fs;
+use std::path::Path;
+use std::process::Stdio;
+
+use crate::{shell, Context};
+
+/// Returns the keystore password from `--password-command` or `--password-file`, if either was
+/// given.
+///
+/// Only the first line of the command's output or the file is used, so trailing line breaks
+/// and e.g. the metadata `pass` stores below the password are ignored.
+pub fn password(context: &Context) -> Option<String> {
+    if let Some(command) = &context.password_command {
+        Some(from_command(command))
+    } else {
+        context.password_file.as_deref().map(from_file)
+    }
+}
+
+/// Runs `command` in the shell and returns the first line it prints.
+fn from_command(command: &str) -> String {
+    let output = shell(command)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("Couldn't run the password command");
+    if !output.status.success() {
+        fail!("The password command {:?} failed with {}", command, output.status);
+    }
+    let stdout = String::from_utf8(output.stdout).expect("The password command printed non-UTF-8");
+    stdout.lines().next().unwrap_or_default().to_string()
+}
+
+/// Returns the first line of the password file, after checking that only its owner can access it.
+fn from_file(path: &Path) -> String {
+    check_permissions(path);
+    let contents = fs::read_to_string(path).expect("Couldn't read the password file");
+    contents.lines().next().unwrap_or_default().to_string()
+}
+
+/// Panics if the file at `path` can be accessed by other users than its owner.
+#[cfg(unix)]
+fn check_permissions(path: &Path) {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)
+        .expect("Couldn't read the password file")
+        .permissions()
+        .mode();
+    if mode & 0o077 != 0 {
+        fail!(
+            "The password file {} can be accessed by other users, restrict it with `chmod 600 {}`",
+            path.display(),
+            path.display()
+        );
+    }
+}
+
+/// Permissions aren't expressed as modes on this platform, so they can't be checked.
+#[cfg(not(unix))]
+fn check_permissions(_path: &Path) {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_dir;
+
+    #[test]
+    fn reads_the_first_line_of_password_files() {
+        let dir = test_dir("secret-first-line");
+        let path = dir.join("password");
+        fs::write(&path, "secret\nnot the password\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        }
+        assert_eq!(from_file(&path), "secret");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[should_panic(expected = "can be accessed by other users")]
+    fn rejects_password_files_others_can_read() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = test_dir("secret-permissions");
+        let path = dir.join("password");
+        fs::write(&path, "secret\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        from_file(&path);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reads_the_first_line_of_password_commands() {
+        assert_eq!(from_command("printf 'secret\\nmetadata\\n'"), "secret");
+    }
+}
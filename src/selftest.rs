@@ -0,0 +1,75 @@
+// @format
+use clap::Args;
+use ethers::{
+    core::types::{transaction::eip712::Eip712, U256},
+    signers::{LocalWallet, Signer},
+    utils::to_checksum,
+};
+use kiwistand_core::{canonical, sign, Message};
+
+use crate::{confirm, Context};
+
+/// `SelftestArgs` contains the node to run the self-test against.
+#[derive(Args)]
+pub struct SelftestArgs {
+    /// The node to submit the canary message to, e.g. a staging deployment.
+    ///
+    /// There's deliberately no default, so the canary never ends up on news.kiwistand.com by
+    /// accident.
+    #[arg(long)]
+    endpoint: String,
+}
+
+/// Prints the outcome of a step and returns whether it passed.
+fn report(step: &str, passed: bool, detail: &str) -> bool {
+    let outcome = if passed { "pass" } else { "fail" };
+    println!("{:<10} {}  {}", step, outcome, detail);
+    passed
+}
+
+/// Signs a canary message with a throwaway key, verifies it and submits it to the endpoint.
+///
+/// Returns whether every step passed. Nodes can't be queried for messages yet, so the submitted
+/// message isn't fetched back.
+pub async fn run(context: &Context, args: &SelftestArgs) -> bool {
+    let wallet = LocalWallet::new(&mut rand::thread_rng());
+    let address = wallet.address();
+    report("key", true, &format!("generated {}", to_checksum(&address, None)));
+
+    let timestamp = context.clock.now();
+    let message = Message {
+        title: String::from("kiwistand-cli selftest"),
+        href: format!("https://example.com/kiwistand-cli-selftest-{}", timestamp),
+        r#type: String::from("amplify"),
+        timestamp: U256::from(timestamp),
+    };
+    let signature = sign(wallet, &message).await;
+    report("sign", true, &format!("message {}", message.id()));
+
+    let digest = message
+        .encode_eip712()
+        .expect("Couldn't compute the EIP-712 digest");
+    let verified = signature.recover(digest).is_ok_and(|signer| signer == address);
+    if !report("verify", verified, "recovered the throwaway key's address") {
+        return false;
+    }
+
+    let endpoint = args.endpoint.trim_end_matches('/');
+    if !confirm(context, &format!("Send a canary message to {}?", endpoint)) {
+        report("submit", false, "aborted, nothing was sent");
+        return false;
+    }
+    let url = format!("{}/messages", endpoint);
+    let payload = canonical::serialize(&message, &signature);
+    let submitted = match context.transport.post_json(&url, payload).await {
+        Ok(response) if response.is_success() => report("submit", true, "the node accepted it"),
+        Ok(response) => report(
+            "submit",
+            false,
+            &format!("the node responded with {}: {}", response.status, response.body.trim()),
+        ),
+        Err(error) => report("submit", false, &format!("couldn't reach the node: {}", error)),
+    };
+    println!("{:<10} skip  nodes can't be queried for messages yet", "fetch");
+    submitted
+}
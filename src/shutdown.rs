@@ -0,0 +1,82 @@
+// @format
+use kiwistand_api::atomic;
+
+use std::path::PathBuf;
+
+/// Makes the process exit with a distinct status when it's interrupted or terminated.
+///
+/// The handler runs on its own task so that it also fires while the main task is blocked, e.g.
+/// waiting for input at a prompt. As exiting skips destructors, it first restores the terminal
+/// settings a hidden prompt may have changed and removes the temporary files of unfinished
+/// writes to the state directory.
+pub fn handle_signals(state_dir: Option<PathBuf>) {
+    let terminal = terminal::save();
+    tokio::spawn(async move {
+        let status = wait_for_signal().await;
+        terminal::restore(&terminal);
+        if let Some(state_dir) = state_dir {
+            atomic::remove_leftovers(&state_dir);
+        }
+        eprintln!("\nInterrupted");
+        std::process::exit(status);
+    });
+}
+
+/// Waits for SIGINT or SIGTERM and returns the conventional exit status for it.
+#[cfg(unix)]
+async fn wait_for_signal() -> i32 {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("Couldn't listen for SIGTERM");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => 130,
+        _ = terminate.recv() => 143,
+    }
+}
+
+/// Waits for Ctrl-C and returns the conventional exit status for it.
+#[cfg(not(unix))]
+async fn wait_for_signal() -> i32 {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("Couldn't listen for Ctrl-C");
+    130
+}
+
+/// Saving and restoring the settings of the terminal on stdin, such as whether it echoes input.
+#[cfg(unix)]
+mod terminal {
+    use std::mem::MaybeUninit;
+
+    /// Returns the current settings, or `None` if stdin isn't a terminal.
+    pub fn save() -> Option<libc::termios> {
+        let mut settings = MaybeUninit::uninit();
+        // SAFETY: tcgetattr only writes to the given struct, and it's only read if that succeeded.
+        unsafe {
+            match libc::tcgetattr(libc::STDIN_FILENO, settings.as_mut_ptr()) {
+                0 => Some(settings.assume_init()),
+                _ => None,
+            }
+        }
+    }
+
+    /// Applies the settings returned by `save`.
+    pub fn restore(settings: &Option<libc::termios>) {
+        if let Some(settings) = settings {
+            // SAFETY: the settings were read by tcgetattr for the same file descriptor.
+            unsafe {
+                libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, settings);
+            }
+        }
+    }
+}
+
+/// The console settings can't be saved on this platform, rpassword restores them itself.
+#[cfg(not(unix))]
+mod terminal {
+    pub fn save() -> Option<()> {
+        None
+    }
+
+    pub fn restore(_settings: &Option<()>) {}
+}
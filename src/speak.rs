@@ -0,0 +1,71 @@
+// @format
+use kiwistand_core::Message;
+
+use std::io::Write;
+use std::process::Stdio;
+
+use crate::{shell, Context};
+
+/// Returns a sentence describing a message, for reading it aloud.
+pub fn summary(message: &Message) -> String {
+    match message.title.as_str() {
+        "" => format!("A vote for {}.", message.href),
+        title => format!("A submission titled {}, linking to {}.", title, message.href),
+    }
+}
+
+/// Pipes `text` into the command given with `--speak`, e.g. a text-to-speech program, which runs
+/// in the shell like `--password-command`.
+///
+/// Failing to run the command only prints a warning, so it can't prevent sending.
+pub fn speak(context: &Context, text: &str) {
+    let command = match &context.speak {
+        Some(command) => command,
+        None => return,
+    };
+    let child = shell(command).stdin(Stdio::piped()).spawn();
+    let result = child.and_then(|mut child| {
+        child.stdin.take().unwrap().write_all(text.as_bytes())?;
+        child.wait()
+    });
+    if let Err(error) = result {
+        eprintln!("Couldn't run the --speak command {:?}: {}", command, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ethers::core::types::U256;
+
+    fn message(title: &str) -> Message {
+        Message {
+            title: String::from(title),
+            href: String::from("https://example.com"),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(1676559616),
+        }
+    }
+
+    #[test]
+    fn summarizes_messages() {
+        assert_eq!(summary(&message("")), "A vote for https://example.com.");
+        assert_eq!(
+            summary(&message("Hello")),
+            "A submission titled Hello, linking to https://example.com."
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn runs_the_command_in_the_shell() {
+        let dir = crate::test_dir("speak");
+        let path = dir.join("spoken text");
+        let command = format!("cat > '{}'", path.display());
+        let context = crate::test_context(&["--speak", &command]);
+        speak(&context, "A vote for https://example.com.");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "A vote for https://example.com.");
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,171 @@
+// @format
+use clap::{Args, Subcommand};
+use kiwistand_api::atomic;
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::{lock, Context};
+
+/// `TemplateArgs` contains the template subcommand to run.
+#[derive(Args)]
+pub struct TemplateArgs {
+    #[command(subcommand)]
+    command: TemplateCommands,
+}
+
+/// The subcommands for managing title templates.
+#[derive(Subcommand)]
+enum TemplateCommands {
+    /// Adds a template, or replaces the one with the same name.
+    Add { name: String, pattern: String },
+    /// Lists all templates.
+    List,
+    /// Removes a template.
+    Remove { name: String },
+}
+
+/// `TemplateOptions` selects a template to expand into the title, together with its variables.
+#[derive(Args)]
+pub struct TemplateOptions {
+    /// Name of the template to build the title from, where `{title}` is the given title.
+    #[arg(long)]
+    template: Option<String>,
+    /// A variable for the template, as `name=value`.
+    #[arg(long = "var", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+}
+
+/// Parses a `name=value` template variable.
+fn parse_var(var: &str) -> Result<(String, String), String> {
+    match var.split_once('=') {
+        Some((name, value)) => Ok((name.to_string(), value.to_string())),
+        None => Err(format!("expected name=value, found `{}`", var)),
+    }
+}
+
+/// Returns the path of the file storing the templates.
+fn templates_path(context: &Context) -> PathBuf {
+    context
+        .state_file("templates.json")
+        .unwrap_or_else(|| fail!("templates need a state directory, see --state-dir"))
+}
+
+/// Reads all templates, keyed by name.
+fn read_templates(context: &Context) -> BTreeMap<String, String> {
+    let path = templates_path(context);
+    if !path.exists() {
+        return BTreeMap::new();
+    }
+    let contents = fs::read_to_string(path).expect("Couldn't read the templates");
+    serde_json::from_str(&contents).expect("Couldn't parse the templates")
+}
+
+/// Stores all templates, replacing the previous ones.
+fn write_templates(context: &Context, templates: &BTreeMap<String, String>) {
+    let path = templates_path(context);
+    let contents = serde_json::to_string_pretty(templates).unwrap();
+    atomic::write(&path, contents).expect("Couldn't write the templates");
+}
+
+/// Runs a template subcommand.
+pub fn run(context: &Context, args: &TemplateArgs) {
+    let _lock = lock::lock_state(context);
+    let mut templates = read_templates(context);
+    match &args.command {
+        TemplateCommands::Add { name, pattern } => {
+            templates.insert(name.clone(), pattern.clone());
+            write_templates(context, &templates);
+        }
+        TemplateCommands::List => {
+            for (name, pattern) in &templates {
+                println!("{:<16} {}", name, pattern);
+            }
+        }
+        TemplateCommands::Remove { name } => {
+            if templates.remove(name).is_none() {
+                fail!("there is no template named `{}`", name);
+            }
+            write_templates(context, &templates);
+        }
+    }
+}
+
+/// Builds the title to sign from the given title and the selected template, if any.
+///
+/// Panics if no title can be built, e.g. because the template uses an undefined variable.
+pub fn apply(context: &Context, options: &TemplateOptions, title: Option<String>) -> String {
+    let name = match &options.template {
+        Some(name) => name,
+        None => return title.unwrap_or_else(|| fail!("title must be provided")),
+    };
+    let templates = read_templates(context);
+    let pattern = match templates.get(name) {
+        Some(pattern) => pattern,
+        None => fail!("there is no template named `{}`", name),
+    };
+    let mut vars = options.vars.clone();
+    if let Some(title) = title {
+        vars.push((String::from("title"), title));
+    }
+    match expand(pattern, &vars) {
+        Ok(title) => title,
+        Err(error) => fail!("Couldn't expand template `{}`: {}", name, error),
+    }
+}
+
+/// Replaces every `{name}` in `pattern` with the value of the variable `name`.
+///
+/// Returns an error naming the first variable that has no value.
+fn expand(pattern: &str, vars: &[(String, String)]) -> Result<String, String> {
+    let mut expanded = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let name = &rest[start + 1..end];
+        let value = vars
+            .iter()
+            .rev()
+            .find(|(var, _)| var == name)
+            .map(|(_, value)| value)
+            .ok_or_else(|| format!("variable `{}` isn't defined", name))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(vars: &[(&str, &str)]) -> Vec<(String, String)> {
+        vars.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn expands_variables() {
+        let vars = vars(&[("version", "1.2"), ("title", "Faster signing")]);
+        let title = expand("Release {version}: {title}", &vars).unwrap();
+        assert_eq!(title, "Release 1.2: Faster signing");
+    }
+
+    #[test]
+    fn rejects_undefined_variables() {
+        let error = expand("Release {version}", &[]).unwrap_err();
+        assert_eq!(error, "variable `version` isn't defined");
+    }
+
+    #[test]
+    fn keeps_unclosed_braces() {
+        assert_eq!(expand("a {b", &[]).unwrap(), "a {b");
+    }
+}
@@ -0,0 +1,129 @@
+// @format
+use clap::{Args, ValueEnum};
+use kiwistand_core::limits;
+
+use crate::{confirm, Context};
+
+/// Separators pages commonly put between an article's title and the site's name.
+const SITE_SEPARATORS: [&str; 4] = [" | ", " - ", " – ", " — "];
+
+/// The most words a trailing segment may have to be considered a site name.
+const MAX_SITE_WORDS: usize = 4;
+
+/// A change that can be applied to a title before it's signed.
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Transform {
+    /// Capitalizes only the first word, keeping words in all caps, e.g. acronyms.
+    SentenceCase,
+    /// Removes a trailing site name, e.g. " | The Verge".
+    StripSite,
+    /// Shortens the title to the nodes' limit, ending it with an ellipsis.
+    Truncate,
+}
+
+/// `TransformOptions` selects the transforms to apply to a title, in order.
+#[derive(Args)]
+pub struct TransformOptions {
+    /// Transforms to apply to the title in the given order, separated by commas.
+    #[arg(
+        long = "transform",
+        value_enum,
+        value_delimiter = ',',
+        env = "KIWISTAND_TITLE_TRANSFORMS"
+    )]
+    transforms: Vec<Transform>,
+}
+
+/// Applies the selected transforms to `title` and returns the title to sign.
+///
+/// If the title changed, the user sees it before and after and can keep the original instead.
+pub fn apply(context: &Context, options: &TransformOptions, title: String) -> String {
+    let transformed = options
+        .transforms
+        .iter()
+        .fold(title.clone(), |title, transform| transform.apply(&title));
+    if transformed == title {
+        return title;
+    }
+    eprintln!("before:    {}\nafter:     {}", title, transformed);
+    if confirm(context, "Use the transformed title?") {
+        transformed
+    } else {
+        title
+    }
+}
+
+impl Transform {
+    /// Returns `title` with this transform applied.
+    fn apply(&self, title: &str) -> String {
+        match self {
+            Transform::SentenceCase => sentence_case(title),
+            Transform::StripSite => strip_site(title),
+            Transform::Truncate => limits::truncate(title, limits::MAX_TITLE_LENGTH),
+        }
+    }
+}
+
+/// Lowercases capitalized words after the first one, and capitalizes the first.
+fn sentence_case(title: &str) -> String {
+    title
+        .split(' ')
+        .enumerate()
+        .map(|(i, word)| {
+            let mut chars = word.chars();
+            let first = match chars.next() {
+                Some(first) => first,
+                None => return String::new(),
+            };
+            let rest = chars.as_str();
+            if i == 0 {
+                first.to_uppercase().chain(rest.chars()).collect()
+            } else if first.is_uppercase() && !rest.chars().any(char::is_uppercase) {
+                first.to_lowercase().chain(rest.chars()).collect()
+            } else {
+                word.to_string()
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Removes the last segment after a site separator, if it's short enough to be a site name.
+fn strip_site(title: &str) -> String {
+    let cut = SITE_SEPARATORS
+        .iter()
+        .filter_map(|separator| title.rfind(separator))
+        .max();
+    match cut {
+        Some(cut) if cut > 0 => {
+            let site = title[cut..].trim_start_matches(|c: char| !c.is_alphanumeric());
+            if site.split_whitespace().count() <= MAX_SITE_WORDS {
+                title[..cut].trim_end().to_string()
+            } else {
+                title.to_string()
+            }
+        }
+        _ => title.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_to_sentence_case() {
+        assert_eq!(sentence_case("why The EVM Is Slow"), "Why the EVM is slow");
+    }
+
+    #[test]
+    fn strips_site_names() {
+        assert_eq!(strip_site("A title | The Verge"), "A title");
+        assert_eq!(strip_site("Rust - A Language — Example Blog"), "Rust - A Language");
+        assert_eq!(
+            strip_site("Title - and a long subtitle that isn't a site name"),
+            "Title - and a long subtitle that isn't a site name"
+        );
+        assert_eq!(strip_site("No site"), "No site");
+    }
+}
@@ -0,0 +1,146 @@
+// @format
+use ethers::{
+    core::types::{transaction::eip712::Eip712, U256},
+    signers::LocalWallet,
+    utils::hex,
+};
+use kiwistand_core::{sign, Message};
+use serde_json::{json, Value};
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// A throwaway private key, the same one the unit tests sign with.
+const TEST_KEY: &str = "ad54bdeade5537fb0a553190159783e45d02d316a992db05cbed606d3ca36b39";
+
+/// The script that signs the test vectors with the JS client's EIP-712 domain and types.
+///
+/// It takes the path of a kiwistand checkout as its argument and resolves `ethers` from there,
+/// supporting both ethers v5 and v6.
+const SCRIPT: &str = r#"
+import { createRequire } from "module";
+import { readFileSync } from "fs";
+import { pathToFileURL } from "url";
+
+const jsPath = process.argv[1];
+const { EIP712_DOMAIN, EIP712_TYPES } = await import(
+  pathToFileURL(`${jsPath}/src/constants.mjs`)
+);
+const ethers = createRequire(`${jsPath}/package.json`)("ethers");
+const encoder = ethers.TypedDataEncoder ?? ethers.utils._TypedDataEncoder;
+
+const input = JSON.parse(readFileSync(0, "utf8"));
+const wallet = new ethers.Wallet(input.key);
+const results = [];
+for (const message of input.messages) {
+  const digest = encoder.hash(EIP712_DOMAIN, EIP712_TYPES, message);
+  const signature = wallet.signTypedData
+    ? await wallet.signTypedData(EIP712_DOMAIN, EIP712_TYPES, message)
+    : await wallet._signTypedData(EIP712_DOMAIN, EIP712_TYPES, message);
+  results.push({ digest, signature });
+}
+console.log(JSON.stringify(results));
+"#;
+
+/// Returns the messages both implementations sign.
+fn vectors() -> Vec<Message> {
+    let titles = ["hello world", "", "\"Quoted\" – ünïcode \\ title"];
+    titles
+        .iter()
+        .enumerate()
+        .map(|(i, title)| Message {
+            title: String::from(*title),
+            href: String::from("https://example.com"),
+            r#type: String::from("amplify"),
+            timestamp: U256::from(1676559616 + i as u64),
+        })
+        .collect()
+}
+
+/// Signs the test vectors with the JS reference client at `js_path` and with this implementation.
+///
+/// Prints the result of each comparison and returns false if any digest or signature differs.
+pub async fn run(js_path: &Path) -> bool {
+    let messages = vectors();
+    let results = run_js(js_path, &messages);
+    let wallet: LocalWallet = TEST_KEY.parse().unwrap();
+
+    let mut matching = true;
+    for (message, result) in messages.iter().zip(results.iter()) {
+        let digest = format!(
+            "0x{}",
+            hex::encode(message.encode_eip712().expect("Couldn't compute the digest"))
+        );
+        let signature = format!("0x{}", sign(wallet.clone(), message).await);
+        let same_digest = result["digest"].as_str() == Some(digest.as_str());
+        let same_signature = result["signature"].as_str() == Some(signature.as_str());
+        if same_digest && same_signature {
+            println!("ok        {:?}", message.title);
+        } else {
+            matching = false;
+            println!("mismatch  {:?}", message.title);
+            println!("  rust    {} {}", digest, signature);
+            println!("  js      {} {}", result["digest"], result["signature"]);
+        }
+    }
+    matching
+}
+
+/// Runs the JS script with node and returns the digest and signature it produced per message.
+fn run_js(js_path: &Path, messages: &[Message]) -> Vec<Value> {
+    let input = json!({
+        "key": format!("0x{}", TEST_KEY),
+        "messages": messages
+            .iter()
+            .map(|message| json!({
+                "title": message.title,
+                "href": message.href,
+                "type": message.r#type,
+                "timestamp": message.timestamp.as_u64(),
+            }))
+            .collect::<Vec<Value>>(),
+    });
+
+    let mut child = Command::new("node")
+        .arg("--input-type=module")
+        .arg("-e")
+        .arg(SCRIPT)
+        .arg(js_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("Couldn't run node, is it installed?");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.to_string().as_bytes())
+        .expect("Couldn't pass the test vectors to node");
+    let output = child
+        .wait_with_output()
+        .expect("Couldn't wait for node to finish");
+    if !output.status.success() {
+        panic!("The JS reference client failed to sign the test vectors");
+    }
+    let results: Vec<Value> =
+        serde_json::from_slice(&output.stdout).expect("Couldn't parse the output of node");
+    assert_eq!(results.len(), messages.len());
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    // Only runs when KIWISTAND_JS_PATH points to a checkout of the kiwistand JS client.
+    #[tokio::test]
+    async fn matches_js_reference_client() {
+        let js_path = match std::env::var_os("KIWISTAND_JS_PATH") {
+            Some(js_path) => PathBuf::from(js_path),
+            None => return,
+        };
+        assert!(run(&js_path).await);
+    }
+}